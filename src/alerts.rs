@@ -0,0 +1,295 @@
+//! Price and spread alerts on products, checked against each new ticker.
+//!
+//! Tickers can be fed in by hand with Alerts::check, from poll_ticker or any other source, or
+//! fetched in the background by AlertPoller.
+
+// std
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+// external
+use rust_decimal::Decimal;
+use tokio::sync::{broadcast, Mutex, MutexGuard};
+use tokio::time::MissedTickBehavior;
+// crate
+use crate::CoinbasePublicClient;
+use crate::background::{Background, CHANNEL_CAPACITY};
+use crate::models::Ticker;
+use crate::product_id::{IntoProductId, ProductId};
+
+/// What an alert waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Last trade price is above the threshold, including on the first ticker checked.
+    PriceAbove(Decimal),
+    /// Last trade price is below the threshold, including on the first ticker checked.
+    PriceBelow(Decimal),
+    /// Last trade price moved from at or below the threshold to above it between two tickers.
+    CrossesAbove(Decimal),
+    /// Last trade price moved from at or above the threshold to below it between two tickers.
+    CrossesBelow(Decimal),
+    /// Spread is wider than this many basis points of the mid price.
+    SpreadAboveBps(Decimal),
+}
+
+impl Condition {
+    /// Whether the condition holds for 'current', given the product's previous ticker.
+    pub fn is_met(&self, previous: Option<&Ticker>, current: &Ticker) -> bool {
+        match *self {
+            Condition::PriceAbove(threshold) => current.price > threshold,
+            Condition::PriceBelow(threshold) => current.price < threshold,
+            Condition::CrossesAbove(threshold) => current.price > threshold
+                && previous.is_some_and(|previous| previous.price <= threshold),
+            Condition::CrossesBelow(threshold) => current.price < threshold
+                && previous.is_some_and(|previous| previous.price >= threshold),
+            Condition::SpreadAboveBps(bps) => current.spread_bps().is_some_and(|spread| spread > bps),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::PriceAbove(threshold) => write!(f, "price above {}", threshold),
+            Condition::PriceBelow(threshold) => write!(f, "price below {}", threshold),
+            Condition::CrossesAbove(threshold) => write!(f, "price crossed above {}", threshold),
+            Condition::CrossesBelow(threshold) => write!(f, "price crossed below {}", threshold),
+            Condition::SpreadAboveBps(bps) => write!(f, "spread above {} bps", bps),
+        }
+    }
+}
+
+/// Identifies a registered alert, for removing it later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AlertId(u64);
+
+/// Passed to an alert's callback when it triggers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triggered {
+    pub alert_id: AlertId,
+    pub product_id: ProductId,
+    pub condition: Condition,
+    /// Ticker that met the condition.
+    pub ticker: Ticker,
+}
+
+type Callback = Box<dyn Fn(Triggered) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+struct Rule {
+    id: AlertId,
+    product_id: ProductId,
+    condition: Condition,
+    callback: Callback,
+    /// Whether the condition held on the last ticker, so it only triggers again after it
+    /// stops holding.
+    met: bool,
+}
+
+/// Registered alerts and the last ticker seen for each product.
+///
+/// An alert triggers when its condition starts holding, and then not again until the
+/// condition has stopped holding on some later ticker, so a price sitting above a threshold
+/// triggers once rather than on every ticker.
+#[derive(Default)]
+pub struct Alerts {
+    rules: Vec<Rule>,
+    last_tickers: HashMap<ProductId, Ticker>,
+    next_id: u64,
+}
+
+impl Alerts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an alert and returns its id.
+    ///
+    /// # Arguments
+    ///
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Fails if it is malformed.
+    /// * 'condition' - what to wait for.
+    /// * 'callback' - async function run with the details each time the alert triggers.
+    ///   Checking waits for it to finish, so long running work should be spawned.
+    pub fn add<F, Fut>(&mut self, product_id: impl IntoProductId, condition: Condition, callback: F) -> Result<AlertId, anyhow::Error>
+    where
+        F: Fn(Triggered) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let id = AlertId(self.next_id);
+        self.next_id += 1;
+        self.rules.push(Rule {
+            id,
+            product_id: product_id.into_product_id()?,
+            condition,
+            callback: Box::new(move |triggered| Box::pin(callback(triggered))),
+            met: false,
+        });
+        Ok(id)
+    }
+
+    /// Removes an alert. Returns false if there was no such alert.
+    pub fn remove(&mut self, id: AlertId) -> bool {
+        let before = self.rules.len();
+        self.rules.retain(|rule| rule.id != id);
+        self.rules.len() != before
+    }
+
+    /// Products with at least one alert, sorted.
+    pub fn product_ids(&self) -> Vec<ProductId> {
+        let product_ids: BTreeSet<&ProductId> = self.rules.iter().map(|rule| &rule.product_id).collect();
+        product_ids.into_iter().cloned().collect()
+    }
+
+    /// Checks a product's alerts against a new ticker, running the callbacks of those that
+    /// trigger in the order they were added. Returns how many triggered.
+    pub async fn check(&mut self, product_id: &ProductId, ticker: &Ticker) -> usize {
+        let previous = self.last_tickers.get(product_id);
+        let mut triggered = Vec::new();
+        for rule in self.rules.iter_mut().filter(|rule| &rule.product_id == product_id) {
+            let met = rule.condition.is_met(previous, ticker);
+            if met && !rule.met {
+                triggered.push((rule.callback)(Triggered {
+                    alert_id: rule.id,
+                    product_id: product_id.clone(),
+                    condition: rule.condition,
+                    ticker: ticker.clone(),
+                }));
+            }
+            rule.met = met;
+        }
+        self.last_tickers.insert(product_id.clone(), ticker.clone());
+
+        let count = triggered.len();
+        for callback in triggered {
+            callback.await;
+        }
+        count
+    }
+}
+
+impl fmt::Debug for Alerts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Alerts")
+            .field("alerts", &self.rules.iter().map(|rule| (rule.id, &rule.product_id, rule.condition)).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Ticker request of an AlertPoller that failed, such as for a product id Coinbase doesn't
+/// know, whose alerts then can't trigger.
+#[derive(Debug, Clone)]
+pub struct TickerFetchFailed {
+    pub product_id: ProductId,
+    pub error: Arc<anyhow::Error>,
+}
+
+/// Fetches the ticker of every product with an alert at a fixed interval and checks the
+/// alerts against it. Requests go through the client's rate limiter, and polls missed while
+/// waiting on it are skipped rather than sent in a burst.
+///
+/// A product whose request fails is reported through errors and checked again on the next
+/// poll. Dropping the poller stops it.
+#[derive(Debug)]
+pub struct AlertPoller {
+    alerts: Arc<Mutex<Alerts>>,
+    errors: Background<TickerFetchFailed>,
+}
+
+impl AlertPoller {
+    /// Spawns the polling task, which needs a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - shared client.
+    /// * 'alerts' - alerts to check. More can be added later through the alerts method.
+    /// * 'interval' - time between polls of all products.
+    pub fn spawn(client: Arc<CoinbasePublicClient>, alerts: Alerts, interval: Duration) -> Self {
+        let alerts = Arc::new(Mutex::new(alerts));
+        let shared_alerts = alerts.clone();
+
+        let errors = Background::spawn(CHANNEL_CAPACITY, |errors| async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticks.tick().await;
+                let product_ids = shared_alerts.lock().await.product_ids();
+                for product_id in product_ids {
+                    let endpoint = format!("/products/{}/ticker", product_id);
+                    match client.get_typed::<Ticker>(&endpoint, None).await {
+                        Ok(ticker) => {
+                            shared_alerts.lock().await.check(&product_id, &ticker).await;
+                        },
+                        Err(err) => errors.publish(TickerFetchFailed { product_id, error: Arc::new(err) }),
+                    }
+                }
+            }
+        });
+
+        Self { alerts, errors }
+    }
+
+    /// Receives each failed ticker request.
+    pub fn errors(&self) -> broadcast::Receiver<TickerFetchFailed> {
+        self.errors.subscribe()
+    }
+
+    /// Locks the alerts for adding or removing some. Callbacks can't call this, since the
+    /// alerts are locked while they run.
+    pub async fn alerts(&self) -> MutexGuard<'_, Alerts> {
+        self.alerts.lock().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    fn ticker(price: i64, bid: i64, ask: i64) -> Ticker {
+        serde_json::from_value(serde_json::json!({
+            "trade_id": 1, "price": price.to_string(), "size": "1", "bid": bid.to_string(),
+            "ask": ask.to_string(), "volume": "100", "time": "2022-10-10T10:00:00Z",
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_alerts_trigger_once_per_crossing() {
+        let product_id: ProductId = "ETH-USD".parse().unwrap();
+        let fired = Arc::new(StdMutex::new(Vec::new()));
+        let mut alerts = Alerts::new();
+        for condition in [Condition::PriceAbove(Decimal::from(100)), Condition::CrossesBelow(Decimal::from(90)), Condition::SpreadAboveBps(Decimal::from(500))] {
+            let fired = fired.clone();
+            alerts.add("eth-usd", condition, move |triggered| {
+                let fired = fired.clone();
+                async move { fired.lock().unwrap().push(triggered.condition.to_string()) }
+            }).unwrap();
+        }
+        let removed = alerts.add("BTC-USD", Condition::PriceAbove(Decimal::ZERO), |_| async {}).unwrap();
+        assert!(alerts.remove(removed));
+        assert_eq!(alerts.product_ids(), std::slice::from_ref(&product_id));
+
+        assert_eq!(alerts.check(&product_id, &ticker(101, 100, 102)).await, 1);
+        assert_eq!(alerts.check(&product_id, &ticker(105, 104, 106)).await, 0);
+        assert_eq!(alerts.check(&product_id, &ticker(85, 80, 90)).await, 2);
+        assert_eq!(alerts.check(&product_id, &ticker(101, 100, 102)).await, 1);
+        assert_eq!(*fired.lock().unwrap(), [
+            "price above 100", "price crossed below 90", "spread above 500 bps", "price above 100",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_poller_reports_failed_tickers() {
+        // no canned ticker, so every request fails
+        let client = Arc::new(CoinbasePublicClient::builder().offline(crate::testing::CannedResponses::new()).build());
+        let mut alerts = Alerts::new();
+        alerts.add("ETH-USD", Condition::PriceAbove(Decimal::ZERO), |_| async {}).unwrap();
+        let poller = AlertPoller::spawn(client, alerts, Duration::from_millis(5));
+
+        let failed = poller.errors().recv().await.unwrap();
+        assert_eq!(failed.product_id.as_str(), "ETH-USD");
+    }
+}
@@ -0,0 +1,267 @@
+//! Streaming market statistics computed from trades and candles.
+
+// std
+use std::collections::VecDeque;
+// external
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+// crate
+use crate::models::{Candle, Trade};
+
+/// Typical price of a candle, (high + low + close) / 3.
+fn typical_price(candle: &Candle) -> Decimal {
+    (candle.high + candle.low + candle.close) / Decimal::from(3)
+}
+
+/// Streaming volume-weighted average price over a rolling time window.
+///
+/// Observations must be pushed in time order. Anything older than 'window' relative to the
+/// latest observation is evicted.
+#[derive(Debug, Clone)]
+pub struct Vwap {
+    window: Duration,
+    samples: VecDeque<(DateTime<Utc>, Decimal, Decimal)>,
+    notional: Decimal,
+    volume: Decimal,
+}
+
+impl Vwap {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+            notional: Decimal::ZERO,
+            volume: Decimal::ZERO,
+        }
+    }
+
+    /// Adds a price/volume observation.
+    pub fn push(&mut self, time: DateTime<Utc>, price: Decimal, volume: Decimal) {
+        self.samples.push_back((time, price, volume));
+        self.notional += price * volume;
+        self.volume += volume;
+
+        while let Some(&(oldest, price, volume)) = self.samples.front() {
+            if oldest > time - self.window {
+                break;
+            }
+            self.samples.pop_front();
+            self.notional -= price * volume;
+            self.volume -= volume;
+        }
+    }
+
+    pub fn push_trade(&mut self, trade: &Trade) {
+        self.push(trade.time, trade.price, trade.size);
+    }
+
+    /// Adds a candle, weighting its typical price by its volume.
+    pub fn push_candle(&mut self, candle: &Candle) {
+        self.push(candle.time, typical_price(candle), candle.volume);
+    }
+
+    /// Total volume within the window.
+    pub fn volume(&self) -> Decimal {
+        self.volume
+    }
+
+    /// Current VWAP, or None if there is no volume within the window.
+    pub fn value(&self) -> Option<Decimal> {
+        if self.volume.is_zero() {
+            None
+        } else {
+            Some(self.notional / self.volume)
+        }
+    }
+}
+
+/// Streaming time-weighted average price over a rolling time window.
+///
+/// Each observed price is assumed to hold until the next observation. Observations must be
+/// pushed in time order.
+#[derive(Debug, Clone)]
+pub struct Twap {
+    window: Duration,
+    samples: VecDeque<(DateTime<Utc>, Decimal)>,
+}
+
+impl Twap {
+    pub fn new(window: Duration) -> Self {
+        Self { window, samples: VecDeque::new() }
+    }
+
+    /// Adds a price observation.
+    pub fn push(&mut self, time: DateTime<Utc>, price: Decimal) {
+        self.samples.push_back((time, price));
+        // keep the last observation at or before the window start, since its price
+        // still holds at the beginning of the window
+        while self.samples.len() > 1 && self.samples[1].0 <= time - self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn push_trade(&mut self, trade: &Trade) {
+        self.push(trade.time, trade.price);
+    }
+
+    /// Adds a candle's typical price, observed at the candle's start time.
+    pub fn push_candle(&mut self, candle: &Candle) {
+        self.push(candle.time, typical_price(candle));
+    }
+
+    /// TWAP over the window ending at the latest observation.
+    pub fn value(&self) -> Option<Decimal> {
+        self.samples.back().and_then(|&(latest, _)| self.value_at(latest))
+    }
+
+    /// TWAP over the window ending at 'now', with the latest price held until 'now'.
+    pub fn value_at(&self, now: DateTime<Utc>) -> Option<Decimal> {
+        let start = now - self.window;
+        let mut weighted = Decimal::ZERO;
+        let mut total = Decimal::ZERO;
+
+        for (i, &(time, price)) in self.samples.iter().enumerate() {
+            let until = self.samples.get(i + 1).map_or(now, |&(next, _)| next).min(now);
+            let from = time.max(start);
+            if until > from {
+                let weight = Decimal::from((until - from).num_milliseconds());
+                weighted += price * weight;
+                total += weight;
+            }
+        }
+
+        if total.is_zero() {
+            self.samples.iter().rev().find(|&&(time, _)| time <= now).map(|&(_, price)| price)
+        } else {
+            Some(weighted / total)
+        }
+    }
+}
+
+/// Streaming volume, trade count, high, low and realized volatility over a rolling time
+/// window of trades.
+///
+/// Trades must be pushed in time order, such as successive pages of 'get_product_trades'
+/// reversed. Anything older than 'window' relative to the latest trade is evicted.
+#[derive(Debug, Clone)]
+pub struct RollingTradeStats {
+    window: Duration,
+    trades: VecDeque<(DateTime<Utc>, Decimal, Decimal)>,
+    volume: Decimal,
+}
+
+impl RollingTradeStats {
+    pub fn new(window: Duration) -> Self {
+        Self { window, trades: VecDeque::new(), volume: Decimal::ZERO }
+    }
+
+    pub fn push_trade(&mut self, trade: &Trade) {
+        self.trades.push_back((trade.time, trade.price, trade.size));
+        self.volume += trade.size;
+
+        while let Some(&(oldest, _, size)) = self.trades.front() {
+            if oldest > trade.time - self.window {
+                break;
+            }
+            self.trades.pop_front();
+            self.volume -= size;
+        }
+    }
+
+    /// Total size traded within the window.
+    pub fn volume(&self) -> Decimal {
+        self.volume
+    }
+
+    /// Number of trades within the window.
+    pub fn trade_count(&self) -> usize {
+        self.trades.len()
+    }
+
+    /// Highest trade price within the window.
+    pub fn high(&self) -> Option<Decimal> {
+        self.trades.iter().map(|&(_, price, _)| price).max()
+    }
+
+    /// Lowest trade price within the window.
+    pub fn low(&self) -> Option<Decimal> {
+        self.trades.iter().map(|&(_, price, _)| price).min()
+    }
+
+    /// Realized volatility within the window: the square root of the sum of squared log
+    /// returns between consecutive trades. Not annualized. None with fewer than two trades.
+    pub fn realized_volatility(&self) -> Option<f64> {
+        if self.trades.len() < 2 {
+            return None;
+        }
+        let prices: Vec<f64> = self.trades.iter().filter_map(|(_, price, _)| price.to_f64()).collect();
+        let variance: f64 = prices.windows(2).map(|pair| (pair[1] / pair[0]).ln().powi(2)).sum();
+        Some(variance.sqrt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1665396000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn test_vwap_window() {
+        let mut vwap = Vwap::new(Duration::seconds(60));
+        assert!(vwap.value().is_none());
+
+        vwap.push(at(0), Decimal::from(100), Decimal::from(1));
+        vwap.push(at(30), Decimal::from(110), Decimal::from(3));
+        assert_eq!(vwap.value(), Some(Decimal::new(1075, 1)));
+
+        // first observation falls out of the window
+        vwap.push(at(60), Decimal::from(120), Decimal::from(1));
+        assert_eq!(vwap.volume(), Decimal::from(4));
+        assert_eq!(vwap.value(), Some(Decimal::new(1125, 1)));
+    }
+
+    #[test]
+    fn test_rolling_trade_stats() {
+        let trade = |seconds: i64, price: i64, size: i64| -> Trade {
+            serde_json::from_value(serde_json::json!({
+                "trade_id": seconds, "time": at(seconds), "price": price.to_string(),
+                "size": size.to_string(), "side": "buy",
+            })).unwrap()
+        };
+        let mut stats = RollingTradeStats::new(Duration::seconds(60));
+        assert!(stats.high().is_none() && stats.realized_volatility().is_none());
+
+        stats.push_trade(&trade(0, 100, 1));
+        stats.push_trade(&trade(30, 110, 2));
+        stats.push_trade(&trade(45, 100, 3));
+        assert_eq!((stats.trade_count(), stats.volume()), (3, Decimal::from(6)));
+        assert_eq!((stats.high(), stats.low()), (Some(Decimal::from(110)), Some(Decimal::from(100))));
+        let expected = (2.0 * 1.1f64.ln().powi(2)).sqrt();
+        assert!((stats.realized_volatility().unwrap() - expected).abs() < 1e-12);
+
+        // first trade falls out of the window
+        stats.push_trade(&trade(60, 105, 1));
+        assert_eq!((stats.trade_count(), stats.volume()), (3, Decimal::from(6)));
+        assert_eq!(stats.low(), Some(Decimal::from(100)));
+    }
+
+    #[test]
+    fn test_twap_window() {
+        let mut twap = Twap::new(Duration::seconds(60));
+        twap.push(at(0), Decimal::from(100));
+        assert_eq!(twap.value(), Some(Decimal::from(100)));
+
+        twap.push(at(45), Decimal::from(120));
+        // 100 for 45s, 120 for 15s
+        assert_eq!(twap.value_at(at(60)), Some(Decimal::from(105)));
+
+        // window is now 30..90: 100 for 15s, 120 for 45s
+        twap.push(at(90), Decimal::from(130));
+        assert_eq!(twap.value(), Some(Decimal::from(115)));
+    }
+}
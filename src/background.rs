@@ -0,0 +1,88 @@
+//! Background tasks shared by the pollers, watchers and monitors.
+
+// std
+use std::future::Future;
+// external
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Number of events buffered for each subscriber before the oldest are dropped, unless a
+/// component picks its own.
+pub(crate) const CHANNEL_CAPACITY: usize = 256;
+
+/// Task spawned on the current tokio runtime and aborted when this is dropped, so components
+/// stop polling as soon as their owner lets go of them.
+#[derive(Debug)]
+pub(crate) struct AbortOnDrop(JoinHandle<()>);
+
+impl AbortOnDrop {
+    pub(crate) fn spawn(task: impl Future<Output = ()> + Send + 'static) -> Self {
+        Self(tokio::spawn(task))
+    }
+}
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Sending half handed to a Background task.
+#[derive(Debug, Clone)]
+pub(crate) struct Publisher<E>(broadcast::Sender<E>);
+
+impl<E> Publisher<E> {
+    pub(crate) fn publish(&self, event: E) {
+        // no subscribers is not an error
+        let _ = self.0.send(event);
+    }
+}
+
+/// Background task publishing events to any number of subscribers. Subscribers that fall
+/// more than the channel capacity behind get a Lagged error and skip ahead. The task is
+/// aborted when this is dropped.
+#[derive(Debug)]
+pub(crate) struct Background<E> {
+    events: broadcast::Sender<E>,
+    _task: AbortOnDrop,
+}
+
+impl<E: Clone + Send + 'static> Background<E> {
+    /// Spawns 'task' with a Publisher for its events.
+    pub(crate) fn spawn<F, Fut>(capacity: usize, task: F) -> Self
+    where
+        F: FnOnce(Publisher<E>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (events, _) = broadcast::channel(capacity);
+        let task = AbortOnDrop::spawn(task(Publisher(events.clone())));
+        Self { events, _task: task }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<E> {
+        self.events.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_stops_on_drop() {
+        let background = Background::spawn(4, |events| async move {
+            for count in 0.. {
+                events.publish(count);
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        });
+        let mut events = background.subscribe();
+        assert!(events.recv().await.is_ok());
+
+        drop(background);
+        // once the task is gone the channel closes, after any events already buffered
+        let closed = async { while let Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) = events.recv().await {} };
+        tokio::time::timeout(Duration::from_secs(1), closed).await.unwrap();
+    }
+}
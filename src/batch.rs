@@ -0,0 +1,87 @@
+//! Running many requests concurrently with a bound on how many are in flight.
+//!
+//! Every request made through a client waits on that client's rate limiter, so requests
+//! started here never exceed the configured rate no matter how many are queued. The bound
+//! on in-flight requests additionally caps open connections and memory use.
+
+// std
+use std::future::Future;
+// external
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+/// Runs futures with at most 'max_in_flight' polled at once, returning their outputs in
+/// input order. A 'max_in_flight' of 0 is treated as 1.
+///
+///             use coinbase_pro_api::{CoinbasePublicClient, OBLevel};
+///             use coinbase_pro_api::batch::join_limited;
+///
+///             # async fn books(client: &CoinbasePublicClient) {
+///             let products = ["ETH-USD", "BTC-USD", "SOL-USD"];
+///             let books = join_limited(
+///                 products.iter().map(|&product| client.get_product_orderbook(product, OBLevel::Level2)),
+///                 2,
+///             ).await;
+///             # }
+pub async fn join_limited<I, F>(futures: I, max_in_flight: usize) -> Vec<F::Output>
+where
+    I: IntoIterator<Item = F>,
+    F: Future,
+{
+    stream::iter(futures)
+        .buffered(max_in_flight.max(1))
+        .collect()
+        .await
+}
+
+/// Like join_limited, but stops at the first error. Futures that haven't completed yet are
+/// dropped, which cancels their requests.
+pub async fn try_join_limited<I, F, T, E>(futures: I, max_in_flight: usize) -> Result<Vec<T>, E>
+where
+    I: IntoIterator<Item = F>,
+    F: Future<Output = Result<T, E>>,
+{
+    stream::iter(futures)
+        .buffered(max_in_flight.max(1))
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_join_limited_bounds_concurrency_and_keeps_order() {
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        let tasks = (0..10u64).map(|i| {
+            let (in_flight, peak) = (&in_flight, &peak);
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(current, Ordering::SeqCst);
+                // later tasks finish first, so order has to be restored
+                tokio::time::sleep(Duration::from_millis(20 - i)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        });
+
+        let results = join_limited(tasks, 3).await;
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_try_join_limited_stops_at_error() {
+        let tasks = (0..5).map(|i| async move {
+            if i == 2 { Err(format!("task {} failed", i)) } else { Ok(i) }
+        });
+        assert_eq!(try_join_limited(tasks, 2).await, Err("task 2 failed".to_owned()));
+
+        let tasks = (0..5).map(|i| async move { Ok::<_, String>(i) });
+        assert_eq!(try_join_limited(tasks, 0).await, Ok(vec![0, 1, 2, 3, 4]));
+    }
+}
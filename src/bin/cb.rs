@@ -0,0 +1,195 @@
+//! Command line client for Coinbase Pro public market data.
+//!
+//! Build with 'cargo install coinbase-pro-api --features cli', then run 'cb help'.
+
+// std
+use std::process::ExitCode;
+// external
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+// crate
+use coinbase_pro_api::{CoinbasePublicClient, Granularity, OBLevel};
+use coinbase_pro_api::models::{Candle, Product, ProductStatus};
+
+const USAGE: &str = "\
+usage: cb <command> [arguments]
+
+commands:
+    time                            server time
+    currencies                      list currencies
+    products                        list markets
+    product <PRODUCT>               single market info
+    ticker <PRODUCT>                last trade and best bid/ask
+    stats <PRODUCT>                 24h stats
+    trades <PRODUCT>                latest trades
+    book <PRODUCT> [--level 1|2|3]  order book (default level 1)
+    candles <PRODUCT> [--start RFC3339] [--end RFC3339]
+            [--granularity 1m|5m|15m|1h|6h|1d] [--csv]
+                                    historic rates
+    product-constants [--quote USD,EUR]
+                                    entries for the products module
+                                    (default quote USD)
+    currency-constants              variants for the currency module
+
+PRODUCT is formatted as BASE-QUOTE, such as ETH-USD.";
+
+/// Command line arguments split into positionals and '--flag [value]' options.
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    /// Flags that take no value.
+    const SWITCHES: [&'static str; 1] = ["csv"];
+
+    fn parse(raw: impl Iterator<Item = String>) -> Result<Self, anyhow::Error> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut raw = raw.peekable();
+        while let Some(arg) = raw.next() {
+            match arg.strip_prefix("--") {
+                Some(flag) if Self::SWITCHES.contains(&flag) => options.push((flag.to_owned(), None)),
+                Some(flag) => {
+                    let value = raw.next().ok_or_else(|| anyhow!("missing value for --{}", flag))?;
+                    options.push((flag.to_owned(), Some(value)));
+                },
+                None => positional.push(arg),
+            }
+        }
+        Ok(Self { positional, options })
+    }
+
+    fn product(&self) -> Result<&str, anyhow::Error> {
+        self.positional.get(1).map(String::as_str).ok_or_else(|| anyhow!("missing PRODUCT argument"))
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.iter()
+            .find(|(flag, _)| flag == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn switch(&self, name: &str) -> bool {
+        self.options.iter().any(|(flag, _)| flag == name)
+    }
+
+    fn datetime(&self, name: &str) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+        self.option(name)
+            .map(|value| {
+                DateTime::parse_from_rfc3339(value)
+                    .map(|datetime| datetime.with_timezone(&Utc))
+                    .with_context(|| format!("invalid --{} '{}', expected RFC3339", name, value))
+            })
+            .transpose()
+    }
+}
+
+fn candles_to_csv(json: &str) -> Result<String, anyhow::Error> {
+    let mut candles: Vec<Candle> = serde_json::from_str(json).context("failed to parse candles")?;
+    candles.sort_by_key(|candle| candle.time);
+
+    let mut csv = String::from("time,open,high,low,close,volume\n");
+    for candle in candles {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            candle.time.to_rfc3339(), candle.open, candle.high, candle.low, candle.close, candle.volume
+        ));
+    }
+    Ok(csv)
+}
+
+/// Formats online markets quoted in any of 'quotes' as product_constants entries.
+fn product_constants(products: &[Product], quotes: &[String]) -> String {
+    let mut ids: Vec<&str> = products.iter()
+        .filter(|product| product.status == ProductStatus::Online && quotes.contains(&product.quote_currency))
+        .map(|product| product.id.as_str())
+        .collect();
+    ids.sort_unstable();
+    ids.iter().map(|id| format!("    {} = \"{}\",\n", id.replace('-', "_"), id)).collect()
+}
+
+/// Formats currency codes as the body of the known_currencies invocation, sorted and wrapped
+/// at 100 columns. Codes that aren't valid identifiers, such as '1INCH', are left out; they
+/// still parse as KnownCurrency::Other.
+fn currency_constants(currencies: &[serde_json::Value]) -> String {
+    let mut codes: Vec<String> = currencies.iter()
+        .filter_map(|currency| currency["id"].as_str())
+        .map(str::to_ascii_uppercase)
+        .filter(|code| code.starts_with(|c: char| c.is_ascii_alphabetic()) && code.chars().all(|c| c.is_ascii_alphanumeric()))
+        .collect();
+    codes.sort_unstable();
+    codes.dedup();
+
+    let mut lines = Vec::new();
+    let mut line = String::from("   ");
+    for code in codes {
+        if line.len() + code.len() + 2 > 100 {
+            lines.push(std::mem::replace(&mut line, String::from("   ")));
+        }
+        line += &format!(" {},", code);
+    }
+    lines.push(line);
+    lines.join("\n") + "\n"
+}
+
+async fn run(args: Args) -> Result<String, anyhow::Error> {
+    let client = CoinbasePublicClient::new();
+    let command = args.positional.first().map(String::as_str).unwrap_or("help");
+
+    match command {
+        "time" => client.get_time().await,
+        "currencies" => client.get_currencies().await,
+        "products" => client.get_products().await,
+        "product" => client.get_product(args.product()?).await,
+        "ticker" => client.get_product_ticker(args.product()?).await,
+        "stats" => client.get_product_24h_stats(args.product()?).await,
+        "trades" => client.get_product_trades(args.product()?, None).await,
+        "book" => {
+            let level = args.option("level").map_or(Ok(OBLevel::Level1), str::parse)?;
+            client.get_product_orderbook(args.product()?, level).await
+        },
+        "candles" => {
+            let granularity = args.option("granularity").map(str::parse::<Granularity>).transpose()?;
+            let json = client.get_product_historic_rates(
+                args.product()?, args.datetime("start")?, args.datetime("end")?, granularity
+            ).await?;
+            if args.switch("csv") { candles_to_csv(&json) } else { Ok(json) }
+        },
+        "product-constants" => {
+            let quotes: Vec<String> = args.option("quote").unwrap_or("USD")
+                .split(',')
+                .map(|quote| quote.trim().to_ascii_uppercase())
+                .collect();
+            let products: Vec<Product> = serde_json::from_str(&client.get_products().await?)
+                .context("failed to parse products")?;
+            Ok(product_constants(&products, &quotes))
+        },
+        "currency-constants" => {
+            let currencies: Vec<serde_json::Value> = serde_json::from_str(&client.get_currencies().await?)
+                .context("failed to parse currencies")?;
+            Ok(currency_constants(&currencies))
+        },
+        "help" | "--help" | "-h" => Ok(USAGE.to_owned()),
+        _ => bail!("unknown command '{}'\n\n{}", command, USAGE),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let result = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => run(args).await,
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(output) => {
+            println!("{}", output.trim_end());
+            ExitCode::SUCCESS
+        },
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            ExitCode::FAILURE
+        },
+    }
+}
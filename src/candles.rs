@@ -0,0 +1,564 @@
+//! Utilities for transforming candle series.
+
+// std
+use std::collections::BTreeSet;
+use std::fmt;
+use std::time::SystemTime;
+// external
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rust_decimal::Decimal;
+// crate
+use crate::Granularity;
+use crate::models::{Candle, Trade};
+use crate::product_id::ProductId;
+
+/// Optional start or end of a candle request range.
+///
+/// Converts from any chrono DateTime regardless of its time zone, from SystemTime and from i64
+/// unix timestamps in seconds. Option<DateTime<Utc>> converts too, so 'None' leaves the bound
+/// unset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeBound(Bound);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Unset,
+    At(DateTime<Utc>),
+    /// Unix timestamp outside of chrono's range, rejected when the request is built.
+    OutOfRange(i64),
+}
+
+impl RangeBound {
+    /// The bound as a UTC datetime, or None if unset.
+    pub fn resolve(self) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+        match self.0 {
+            Bound::Unset => Ok(None),
+            Bound::At(time) => Ok(Some(time)),
+            Bound::OutOfRange(timestamp) => Err(anyhow!("timestamp {} is out of range", timestamp)),
+        }
+    }
+}
+
+impl From<Option<DateTime<Utc>>> for RangeBound {
+    fn from(time: Option<DateTime<Utc>>) -> Self {
+        Self(time.map_or(Bound::Unset, Bound::At))
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for RangeBound {
+    fn from(time: DateTime<Tz>) -> Self {
+        Self(Bound::At(time.with_timezone(&Utc)))
+    }
+}
+
+impl From<SystemTime> for RangeBound {
+    fn from(time: SystemTime) -> Self {
+        Self(Bound::At(time.into()))
+    }
+}
+
+impl From<i64> for RangeBound {
+    fn from(timestamp: i64) -> Self {
+        match Utc.timestamp_opt(timestamp, 0).single() {
+            Some(time) => Self(Bound::At(time)),
+            None => Self(Bound::OutOfRange(timestamp)),
+        }
+    }
+}
+
+/// Most candles Coinbase returns for a single historic rates request.
+pub const MAX_CANDLES_PER_REQUEST: u32 = 300;
+
+/// Error returned before sending a historic rates request that Coinbase would reject for
+/// spanning more than MAX_CANDLES_PER_REQUEST candles. Retrieve it with anyhow's downcast_ref.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyCandles {
+    pub requested: u64,
+    pub max: u32,
+}
+
+impl fmt::Display for TooManyCandles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "range spans {} candles but Coinbase returns at most {} per request; \
+             split it into smaller ranges or use a larger granularity",
+            self.requested, self.max
+        )
+    }
+}
+
+impl std::error::Error for TooManyCandles {}
+
+/// Number of candles of the given granularity needed to cover 'start' to 'end', counting a
+/// partial candle at the end as a whole one.
+pub fn candle_count(start: DateTime<Utc>, end: DateTime<Utc>, granularity: Granularity) -> u64 {
+    let span = (end - start).num_seconds().max(0) as u64;
+    let seconds = granularity.seconds() as u64;
+    span.div_ceil(seconds)
+}
+
+/// Checks a historic rates range before it is requested.
+pub(crate) fn validate_range(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    granularity: Granularity,
+) -> Result<(), anyhow::Error> {
+    if end < start {
+        bail!("start {} is after end {}", start, end);
+    }
+    let requested = candle_count(start, end, granularity);
+    if requested > MAX_CANDLES_PER_REQUEST as u64 {
+        return Err(TooManyCandles { requested, max: MAX_CANDLES_PER_REQUEST }.into());
+    }
+    Ok(())
+}
+
+/// Splits 'start' to 'end' (inclusive) into consecutive inclusive ranges that each hold at
+/// most MAX_CANDLES_PER_REQUEST candle start times, oldest first. Ranges don't overlap: each
+/// starts one candle after the previous one ends. Empty if 'end' is before 'start'.
+pub(crate) fn request_chunks(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    granularity: Granularity,
+) -> impl Iterator<Item = (DateTime<Utc>, DateTime<Utc>)> {
+    let step = Duration::seconds(granularity.seconds() as i64);
+    let span = step * (MAX_CANDLES_PER_REQUEST as i32 - 1);
+    let mut chunk_start = start;
+    std::iter::from_fn(move || {
+        if chunk_start > end {
+            return None;
+        }
+        let chunk = (chunk_start, end.min(chunk_start + span));
+        chunk_start = chunk.1 + step;
+        Some(chunk)
+    })
+}
+
+/// Returns the start of the bucket of the given size (in seconds) that contains 'time'.
+/// Buckets are aligned to the unix epoch, matching Coinbase's own candle boundaries.
+pub(crate) fn bucket_start(time: DateTime<Utc>, seconds: i64) -> DateTime<Utc> {
+    let timestamp = time.timestamp();
+    Utc.timestamp_opt(timestamp - timestamp.rem_euclid(seconds), 0).unwrap()
+}
+
+/// Aggregates candles into larger candles: first open, max high, min low, last close and summed volume.
+///
+/// # Arguments
+///
+/// * 'candles' - candles of 'from' granularity, in any order.
+/// * 'from' - granularity of the input candles.
+/// * 'to' - granularity of the output candles. Must be a multiple of 'from'.
+///
+/// Returned candles are sorted oldest first and aligned to 'to' boundaries. Buckets with no
+/// input candles are omitted, just as Coinbase omits periods with no trades.
+pub fn resample(candles: &[Candle], from: Granularity, to: Granularity) -> Result<Vec<Candle>, anyhow::Error> {
+    resample_to_duration(candles, from, Duration::seconds(to.seconds() as i64))
+}
+
+/// Like resample, but into candles of any duration that is a multiple of 'from', such as
+/// 2 hours, 4 hours or 1 week. Buckets are aligned to the unix epoch, so for example weekly
+/// candles start on Thursdays at 00:00 UTC.
+pub fn resample_to_duration(candles: &[Candle], from: Granularity, to: Duration) -> Result<Vec<Candle>, anyhow::Error> {
+    let from_secs = from.seconds() as i64;
+    let to_secs = to.num_seconds();
+    if to_secs < from_secs || to_secs % from_secs != 0 || Duration::seconds(to_secs) != to {
+        bail!("cannot resample {:?} candles into {} candles", from, to);
+    }
+    if let Some(candle) = candles.iter().find(|candle| candle.time.timestamp() % from_secs != 0) {
+        bail!("candle at {} is not aligned to {:?}", candle.time, from);
+    }
+
+    let mut sorted = candles.to_vec();
+    sorted.sort_by_key(|candle| candle.time);
+
+    let mut resampled: Vec<Candle> = Vec::new();
+    for candle in sorted {
+        let start = bucket_start(candle.time, to_secs);
+        match resampled.last_mut() {
+            Some(current) if current.time == start => {
+                current.high = current.high.max(candle.high);
+                current.low = current.low.min(candle.low);
+                current.close = candle.close;
+                current.volume += candle.volume;
+            },
+            _ => resampled.push(Candle { time: start, ..candle }),
+        }
+    }
+
+    Ok(resampled)
+}
+
+/// Largest granularity Coinbase offers that evenly divides 'duration', which is the cheapest
+/// one to fetch and resample into candles of that duration.
+pub fn source_granularity(duration: Duration) -> Result<Granularity, anyhow::Error> {
+    let seconds = duration.num_seconds();
+    Granularity::ALL.into_iter()
+        .rev()
+        .find(|granularity| Duration::seconds(seconds) == duration && seconds % granularity.seconds() as i64 == 0)
+        .filter(|_| seconds > 0)
+        .ok_or_else(|| anyhow!("no supported granularity divides {}", duration))
+}
+
+/// Policy used by fill_gaps for periods where Coinbase published no candle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapPolicy {
+    /// Insert a flat candle at the previous close with zero volume.
+    CarryForward,
+    /// Leave the period as None.
+    Empty,
+}
+
+/// Places candles onto a regular time index, filling missing periods according to 'policy'.
+///
+/// # Arguments
+///
+/// * 'candles' - candles of the given granularity, in any order.
+/// * 'granularity' - candle size, which is also the spacing of the output index.
+/// * 'start' - start of the range. Rounded down to a candle boundary.
+/// * 'end' - end of the range (inclusive).
+/// * 'policy' - how to fill periods without a candle.
+///
+/// Element i of the result corresponds to the candle starting at start + i * granularity.
+/// With GapPolicy::CarryForward, gaps at the very beginning of the range stay None unless a
+/// candle from before 'start' is included in 'candles' to carry forward from.
+pub fn fill_gaps(
+    candles: &[Candle],
+    granularity: Granularity,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    policy: GapPolicy,
+) -> Vec<Option<Candle>> {
+    let seconds = granularity.seconds() as i64;
+    let step = Duration::seconds(seconds);
+
+    let mut slots = Vec::new();
+    let mut slot = bucket_start(start, seconds);
+    while slot <= end {
+        slots.push(slot);
+        slot += step;
+    }
+    place_on_index(candles, &slots, policy)
+}
+
+/// Places candles onto the given ascending times, filling times without a candle according
+/// to 'policy'.
+fn place_on_index(candles: &[Candle], times: &[DateTime<Utc>], policy: GapPolicy) -> Vec<Option<Candle>> {
+    let mut sorted = candles.to_vec();
+    sorted.sort_by_key(|candle| candle.time);
+    let mut candles = sorted.into_iter().peekable();
+
+    let mut last_close: Option<Decimal> = None;
+    times.iter().map(|&slot| {
+        // consume anything up to and including this slot, remembering the latest close
+        let mut current = None;
+        while let Some(candle) = candles.next_if(|candle| candle.time <= slot) {
+            last_close = Some(candle.close);
+            if candle.time == slot {
+                current = Some(candle);
+            }
+        }
+
+        match (current, policy, last_close) {
+            (Some(candle), _, _) => Some(candle),
+            (None, GapPolicy::CarryForward, Some(close)) => Some(Candle {
+                time: slot,
+                low: close,
+                high: close,
+                open: close,
+                close,
+                volume: Decimal::ZERO,
+            }),
+            _ => None,
+        }
+    }).collect()
+}
+
+/// Which times align_candles puts on the common index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlignIndex {
+    /// Only times every product has a candle for, so there are no gaps.
+    Intersection,
+    /// Times any product has a candle for. Gaps are filled according to a GapPolicy.
+    Union,
+}
+
+/// Candles of several products on a common time index, stored column by column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlignedCandles {
+    /// The common index, oldest first.
+    pub times: Vec<DateTime<Utc>>,
+    pub product_ids: Vec<ProductId>,
+    /// One column per product, in the order of 'product_ids', with one entry per time.
+    pub columns: Vec<Vec<Option<Candle>>>,
+}
+
+impl AlignedCandles {
+    /// Column of a single product, or None if it isn't part of the alignment.
+    pub fn column(&self, product_id: &ProductId) -> Option<&[Option<Candle>]> {
+        let i = self.product_ids.iter().position(|id| id == product_id)?;
+        Some(&self.columns[i])
+    }
+
+    /// Closing prices of a single product, with one entry per time.
+    pub fn closes(&self, product_id: &ProductId) -> Option<Vec<Option<Decimal>>> {
+        let column = self.column(product_id)?;
+        Some(column.iter().map(|candle| candle.as_ref().map(|candle| candle.close)).collect())
+    }
+}
+
+/// Aligns candle series of several products, such as for correlation analysis.
+///
+/// # Arguments
+///
+/// * 'series' - candles of the same granularity for each product, in any order.
+/// * 'index' - which times to keep.
+/// * 'policy' - how to fill gaps when 'index' is Union. With GapPolicy::CarryForward, gaps
+///   before a product's first candle stay None.
+pub fn align_candles(series: &[(ProductId, Vec<Candle>)], index: AlignIndex, policy: GapPolicy) -> AlignedCandles {
+    let times_of = |candles: &[Candle]| candles.iter().map(|candle| candle.time).collect::<BTreeSet<_>>();
+    let times: BTreeSet<DateTime<Utc>> = match index {
+        AlignIndex::Union => series.iter().flat_map(|(_, candles)| times_of(candles)).collect(),
+        AlignIndex::Intersection => series.iter()
+            .map(|(_, candles)| times_of(candles))
+            .reduce(|common, times| common.intersection(&times).copied().collect())
+            .unwrap_or_default(),
+    };
+    let times: Vec<DateTime<Utc>> = times.into_iter().collect();
+
+    AlignedCandles {
+        product_ids: series.iter().map(|(product_id, _)| product_id.clone()).collect(),
+        columns: series.iter().map(|(_, candles)| place_on_index(candles, &times, policy)).collect(),
+        times,
+    }
+}
+
+/// Builds candles of any duration from a stream of trades, such as successive pages of
+/// 'get_product_trades'. Useful for bar intervals Coinbase does not offer, like 10 seconds or 2 hours.
+///
+/// Trades must be pushed in trade_id order. Trades that belong to a bucket older than the
+/// current candle are ignored.
+#[derive(Debug, Clone)]
+pub struct CandleAggregator {
+    seconds: i64,
+    current: Option<Candle>,
+}
+
+impl CandleAggregator {
+    /// # Arguments
+    ///
+    /// * 'duration' - candle duration. Must be a positive whole number of seconds.
+    pub fn new(duration: Duration) -> Result<Self, anyhow::Error> {
+        let seconds = duration.num_seconds();
+        if seconds <= 0 || Duration::seconds(seconds) != duration {
+            bail!("candle duration must be a positive whole number of seconds, got {}", duration);
+        }
+        Ok(Self { seconds, current: None })
+    }
+
+    /// Candle duration.
+    pub fn duration(&self) -> Duration {
+        Duration::seconds(self.seconds)
+    }
+
+    /// The partial candle currently being built, if any.
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    /// Adds a trade to the current candle. Returns the previous candle once a trade arrives
+    /// that belongs to a later bucket.
+    pub fn push(&mut self, trade: &Trade) -> Option<Candle> {
+        let start = bucket_start(trade.time, self.seconds);
+        match &mut self.current {
+            Some(current) if start < current.time => None,
+            Some(current) if start == current.time => {
+                current.high = current.high.max(trade.price);
+                current.low = current.low.min(trade.price);
+                current.close = trade.price;
+                current.volume += trade.size;
+                None
+            },
+            _ => self.current.replace(Candle {
+                time: start,
+                low: trade.price,
+                high: trade.price,
+                open: trade.price,
+                close: trade.price,
+                volume: trade.size,
+            }),
+        }
+    }
+
+    /// Returns and clears the current candle, whether or not its bucket has ended.
+    pub fn flush(&mut self) -> Option<Candle> {
+        self.current.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // newest first, as returned by Coinbase
+    const ONE_MINUTE_CANDLES: &str = r#"[
+        [1665396300, 101, 104, 102, 103, 2.5],
+        [1665396240, 99, 102, 100, 101, 1],
+        [1665396180, 100, 103, 101, 100, 1],
+        [1665396120, 98, 101, 99, 101, 0.5],
+        [1665396060, 97, 100, 98, 99, 2],
+        [1665396000, 96, 99, 97, 98, 3]
+    ]"#;
+
+    #[test]
+    fn test_resample_minute_to_5_minutes() {
+        let candles: Vec<Candle> = serde_json::from_str(ONE_MINUTE_CANDLES).unwrap();
+        let resampled = resample(&candles, Granularity::Minute1, Granularity::Minute5).unwrap();
+
+        assert_eq!(resampled.len(), 2);
+        let first = &resampled[0];
+        assert_eq!(first.time.timestamp(), 1665396000);
+        assert_eq!(first.open, Decimal::from(97));
+        assert_eq!(first.high, Decimal::from(103));
+        assert_eq!(first.low, Decimal::from(96));
+        assert_eq!(first.close, Decimal::from(101));
+        assert_eq!(first.volume, Decimal::new(75, 1));
+        assert_eq!(resampled[1].time.timestamp(), 1665396300);
+        assert_eq!(resampled[1].volume, Decimal::new(25, 1));
+    }
+
+    #[test]
+    fn test_resample_to_custom_duration() {
+        let candles: Vec<Candle> = serde_json::from_str(ONE_MINUTE_CANDLES).unwrap();
+        let resampled = resample_to_duration(&candles, Granularity::Minute1, Duration::minutes(10)).unwrap();
+        assert_eq!(resampled.len(), 1);
+        assert_eq!(resampled[0].volume, candles.iter().map(|candle| candle.volume).sum::<Decimal>());
+
+        assert_eq!(source_granularity(Duration::hours(4)).unwrap(), Granularity::Hour1);
+        assert_eq!(source_granularity(Duration::weeks(1)).unwrap(), Granularity::Hour24);
+        assert_eq!(source_granularity(Duration::minutes(45)).unwrap(), Granularity::Minute15);
+        assert!(source_granularity(Duration::seconds(30)).is_err());
+        assert!(source_granularity(Duration::seconds(90)).is_err());
+    }
+
+    #[test]
+    fn test_resample_rejects_invalid_granularities() {
+        let candles: Vec<Candle> = serde_json::from_str(ONE_MINUTE_CANDLES).unwrap();
+        assert!(resample(&candles, Granularity::Minute5, Granularity::Minute1).is_err());
+        assert!(resample(&candles, Granularity::Minute5, Granularity::Hour1).is_err());
+        assert!(resample_to_duration(&candles, Granularity::Minute1, Duration::seconds(90)).is_err());
+    }
+
+    #[test]
+    fn test_fill_gaps() {
+        let candles: Vec<Candle> = serde_json::from_str(r#"[
+            [1665396180, 100, 103, 101, 100, 1],
+            [1665396060, 97, 100, 98, 99, 2]
+        ]"#).unwrap();
+        let start = Utc.timestamp_opt(1665396000, 0).unwrap();
+        let end = Utc.timestamp_opt(1665396240, 0).unwrap();
+
+        let carried = fill_gaps(&candles, Granularity::Minute1, start, end, GapPolicy::CarryForward);
+        assert_eq!(carried.len(), 5);
+        assert!(carried[0].is_none());
+        assert_eq!(carried[1].as_ref().unwrap().volume, Decimal::from(2));
+        let gap = carried[2].as_ref().unwrap();
+        assert_eq!(gap.time.timestamp(), 1665396120);
+        assert_eq!((gap.open, gap.close, gap.volume), (Decimal::from(99), Decimal::from(99), Decimal::ZERO));
+        assert_eq!(carried[4].as_ref().unwrap().close, Decimal::from(100));
+
+        let empty = fill_gaps(&candles, Granularity::Minute1, start, end, GapPolicy::Empty);
+        let present: Vec<bool> = empty.iter().map(Option::is_some).collect();
+        assert_eq!(present, vec![false, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_align_candles() {
+        let eth: Vec<Candle> = serde_json::from_str("[[1665396000, 1, 1, 1, 10, 1], [1665396060, 1, 1, 1, 11, 1], [1665396180, 1, 1, 1, 13, 1]]").unwrap();
+        let btc: Vec<Candle> = serde_json::from_str("[[1665396060, 1, 1, 1, 21, 1], [1665396120, 1, 1, 1, 22, 1], [1665396180, 1, 1, 1, 23, 1]]").unwrap();
+        let (eth_id, btc_id): (ProductId, ProductId) = ("ETH-USD".parse().unwrap(), "BTC-USD".parse().unwrap());
+        let series = [(eth_id.clone(), eth), (btc_id.clone(), btc)];
+        let closes = |values: &[Option<i64>]| values.iter().map(|close| close.map(Decimal::from)).collect::<Vec<_>>();
+
+        let common = align_candles(&series, AlignIndex::Intersection, GapPolicy::Empty);
+        assert_eq!(common.times.iter().map(DateTime::timestamp).collect::<Vec<_>>(), [1665396060, 1665396180]);
+        assert_eq!(common.closes(&eth_id).unwrap(), closes(&[Some(11), Some(13)]));
+
+        let all = align_candles(&series, AlignIndex::Union, GapPolicy::CarryForward);
+        assert_eq!(all.times.len(), 4);
+        assert_eq!(all.closes(&eth_id).unwrap(), closes(&[Some(10), Some(11), Some(11), Some(13)]));
+        assert_eq!(all.closes(&btc_id).unwrap(), closes(&[None, Some(21), Some(22), Some(23)]));
+        assert!(all.column(&"SOL-USD".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_candle_aggregator() {
+        let trades: Vec<Trade> = serde_json::from_str(r#"[
+            {"time":"2022-10-10T10:00:01Z","trade_id":1,"price":"100","size":"1","side":"buy"},
+            {"time":"2022-10-10T10:00:04Z","trade_id":2,"price":"102","size":"2","side":"sell"},
+            {"time":"2022-10-10T10:00:09Z","trade_id":3,"price":"99","size":"1","side":"buy"},
+            {"time":"2022-10-10T10:00:12Z","trade_id":4,"price":"101","size":"3","side":"buy"}
+        ]"#).unwrap();
+
+        let mut aggregator = CandleAggregator::new(Duration::seconds(10)).unwrap();
+        let closed: Vec<Candle> = trades.iter().filter_map(|trade| aggregator.push(trade)).collect();
+
+        assert_eq!(closed.len(), 1);
+        let candle = &closed[0];
+        assert_eq!(candle.time, Utc.with_ymd_and_hms(2022, 10, 10, 10, 0, 0).unwrap());
+        assert_eq!(
+            (candle.open, candle.high, candle.low, candle.close, candle.volume),
+            (Decimal::from(100), Decimal::from(102), Decimal::from(99), Decimal::from(99), Decimal::from(4))
+        );
+
+        let partial = aggregator.current().unwrap();
+        assert_eq!(partial.time, Utc.with_ymd_and_hms(2022, 10, 10, 10, 0, 10).unwrap());
+        assert_eq!(partial.volume, Decimal::from(3));
+        assert!(aggregator.flush().is_some());
+        assert!(aggregator.current().is_none());
+
+        assert!(CandleAggregator::new(Duration::milliseconds(1500)).is_err());
+    }
+
+    #[test]
+    fn test_range_bound_conversions() {
+        let expected = Utc.timestamp_opt(1665396000, 0).unwrap();
+        let offset = DateTime::parse_from_rfc3339("2022-10-10T12:00:00+02:00").unwrap();
+        let system_time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1665396000);
+
+        assert_eq!(RangeBound::from(1665396000).resolve().unwrap(), Some(expected));
+        assert_eq!(RangeBound::from(offset).resolve().unwrap(), Some(expected));
+        assert_eq!(RangeBound::from(system_time).resolve().unwrap(), Some(expected));
+        assert_eq!(RangeBound::from(None).resolve().unwrap(), None);
+        assert!(RangeBound::from(i64::MAX).resolve().is_err());
+    }
+
+    #[test]
+    fn test_request_chunks() {
+        let start = Utc.timestamp_opt(1665396000, 0).unwrap();
+        let chunks: Vec<_> = request_chunks(start, start + Duration::minutes(650), Granularity::Minute1).collect();
+        assert_eq!(chunks, [
+            (start, start + Duration::minutes(299)),
+            (start + Duration::minutes(300), start + Duration::minutes(599)),
+            (start + Duration::minutes(600), start + Duration::minutes(650)),
+        ]);
+        assert!(chunks.iter().all(|&(from, to)| candle_count(from, to, Granularity::Minute1) < MAX_CANDLES_PER_REQUEST as u64));
+        assert_eq!(request_chunks(start, start, Granularity::Minute1).count(), 1);
+        assert_eq!(request_chunks(start, start - Duration::minutes(1), Granularity::Minute1).count(), 0);
+    }
+
+    #[test]
+    fn test_validate_range() {
+        let start = Utc.timestamp_opt(1665396000, 0).unwrap();
+
+        assert_eq!(candle_count(start, start + Duration::minutes(300), Granularity::Minute1), 300);
+        assert_eq!(candle_count(start, start + Duration::seconds(61), Granularity::Minute1), 2);
+        assert!(validate_range(start, start + Duration::minutes(300), Granularity::Minute1).is_ok());
+        assert!(validate_range(start, start - Duration::minutes(1), Granularity::Minute1).is_err());
+
+        let err = validate_range(start, start + Duration::days(2), Granularity::Minute5).unwrap_err();
+        let too_many = err.downcast_ref::<TooManyCandles>().unwrap();
+        assert_eq!((too_many.requested, too_many.max), (576, 300));
+    }
+}
@@ -0,0 +1,137 @@
+//! Sharing of identical in-flight GET requests between concurrent callers.
+
+// std
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+// external
+use anyhow::anyhow;
+use url::Url;
+use tokio::sync::oneshot;
+
+type Waiters = Vec<oneshot::Sender<Result<String, String>>>;
+
+/// Requests currently being sent, keyed by url, with the callers waiting on each.
+///
+/// The first caller for a url sends the request and hands a copy of the result to everyone
+/// who asked for the same url in the meantime. Errors are passed on as their message, since
+/// anyhow errors can't be cloned.
+#[derive(Debug, Default)]
+pub(crate) struct InFlight {
+    requests: Mutex<HashMap<Url, Waiters>>,
+}
+
+impl InFlight {
+    /// Runs 'request' for 'url' unless the same url is already being requested, in which case
+    /// the result of that request is returned instead. If the caller sending the request is
+    /// cancelled, one of the waiting callers sends it in its place.
+    pub(crate) async fn run<F, Fut>(&self, url: Url, request: F) -> Result<String, anyhow::Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String, anyhow::Error>>,
+    {
+        loop {
+            let receiver = {
+                let mut requests = self.requests.lock().unwrap();
+                match requests.get_mut(&url) {
+                    Some(waiters) => {
+                        let (sender, receiver) = oneshot::channel();
+                        waiters.push(sender);
+                        receiver
+                    },
+                    None => {
+                        requests.insert(url.clone(), Vec::new());
+                        break;
+                    },
+                }
+            };
+            match receiver.await {
+                Ok(result) => return result.map_err(|message| anyhow!(message)),
+                // the sending caller was cancelled, so try again
+                Err(_) => continue,
+            }
+        }
+
+        let guard = Leader { in_flight: self, url };
+        let result = request().await;
+        for waiter in guard.finish() {
+            let _ = waiter.send(result.as_ref().map(String::clone).map_err(|err| format!("{:#}", err)));
+        }
+        result
+    }
+}
+
+/// Removes the in-flight entry when the sending caller finishes or is dropped. Dropping the
+/// waiters' senders without a result wakes them up to retry.
+struct Leader<'a> {
+    in_flight: &'a InFlight,
+    url: Url,
+}
+
+impl Leader<'_> {
+    fn finish(self) -> Waiters {
+        self.in_flight.requests.lock().unwrap().remove(&self.url).unwrap_or_default()
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        self.in_flight.requests.lock().unwrap().remove(&self.url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use futures::future::join_all;
+
+    fn url() -> Url {
+        Url::parse("https://api.pro.coinbase.com/products/ETH-USD/book?level=3").unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_share_one_call() {
+        let in_flight = InFlight::default();
+        let calls = AtomicUsize::new(0);
+
+        let request = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok("book".to_owned())
+        };
+        let results = join_all((0..5).map(|_| in_flight.run(url(), request))).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| result.as_deref().unwrap() == "book"));
+
+        // errors are shared too
+        let failing = || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(anyhow!("connection reset"))
+        };
+        let results = join_all((0..2).map(|_| in_flight.run(url(), failing))).await;
+        assert!(results.iter().all(|result| result.as_ref().unwrap_err().to_string() == "connection reset"));
+
+        // finished requests are not cached
+        assert!(in_flight.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_waiter_takes_over_from_cancelled_request() {
+        let in_flight = InFlight::default();
+
+        let slow = in_flight.run(url(), || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok("slow".to_owned())
+        });
+        let waiter = async {
+            tokio::task::yield_now().await;
+            in_flight.run(url(), || async { Ok("fast".to_owned()) }).await
+        };
+
+        let (cancelled, result) = tokio::join!(tokio::time::timeout(Duration::from_millis(20), slow), waiter);
+        assert!(cancelled.is_err());
+        assert_eq!(result.unwrap(), "fast");
+    }
+}
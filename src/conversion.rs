@@ -0,0 +1,196 @@
+//! Currency conversion through chains of Coinbase markets.
+
+// std
+use std::collections::{HashMap, HashSet, VecDeque};
+// external
+use anyhow::anyhow;
+use rust_decimal::Decimal;
+// crate
+use crate::models::{Product, ProductStatus};
+
+/// One step of a conversion path, trading through a single market.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leg {
+    pub product_id: String,
+    pub from: String,
+    pub to: String,
+    /// True when this leg buys the product's base currency with its quote currency, meaning
+    /// the market price has to be inverted.
+    pub inverted: bool,
+}
+
+/// A priced step of a conversion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hop {
+    pub leg: Leg,
+    /// Units of 'to' received per unit of 'from'.
+    pub rate: Decimal,
+}
+
+/// Result of converting an amount between two currencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    pub from: String,
+    pub to: String,
+    pub amount: Decimal,
+    pub converted: Decimal,
+    /// Effective rate across all hops, in units of 'to' per unit of 'from'.
+    pub rate: Decimal,
+    pub hops: Vec<Hop>,
+}
+
+/// Graph of currencies connected by online markets. Build one from 'get_products' and reuse it,
+/// since the product list rarely changes.
+#[derive(Debug, Clone, Default)]
+pub struct ProductGraph {
+    edges: HashMap<String, Vec<Leg>>,
+}
+
+impl ProductGraph {
+    /// Builds a graph from a product list, skipping markets that are not online.
+    pub fn new(products: &[Product]) -> Self {
+        let mut edges: HashMap<String, Vec<Leg>> = HashMap::new();
+        for product in products.iter().filter(|product| product.status == ProductStatus::Online && !product.trading_disabled) {
+            let (base, quote) = (&product.base_currency, &product.quote_currency);
+            edges.entry(base.to_owned()).or_default().push(Leg {
+                product_id: product.id.to_owned(),
+                from: base.to_owned(),
+                to: quote.to_owned(),
+                inverted: false,
+            });
+            edges.entry(quote.to_owned()).or_default().push(Leg {
+                product_id: product.id.to_owned(),
+                from: quote.to_owned(),
+                to: base.to_owned(),
+                inverted: true,
+            });
+        }
+        Self { edges }
+    }
+
+    /// Currencies present in the graph.
+    pub fn currencies(&self) -> impl Iterator<Item = &str> {
+        self.edges.keys().map(String::as_str)
+    }
+
+    /// Finds a path with the fewest hops between two currencies. Currency codes are case-insensitive.
+    /// Returns an empty path when both currencies are the same.
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<Leg>> {
+        let (from, to) = (from.to_uppercase(), to.to_uppercase());
+        if from == to {
+            return Some(Vec::new());
+        }
+
+        let mut previous: HashMap<&str, &Leg> = HashMap::new();
+        let mut visited: HashSet<&str> = HashSet::from([from.as_str()]);
+        let mut queue: VecDeque<&str> = VecDeque::from([from.as_str()]);
+
+        while let Some(currency) = queue.pop_front() {
+            for leg in self.edges.get(currency).into_iter().flatten() {
+                if !visited.insert(leg.to.as_str()) {
+                    continue;
+                }
+                previous.insert(leg.to.as_str(), leg);
+                if leg.to == to {
+                    let mut path = vec![leg.to_owned()];
+                    while let Some(&leg) = previous.get(path[0].from.as_str()) {
+                        path.insert(0, leg.to_owned());
+                    }
+                    return Some(path);
+                }
+                queue.push_back(leg.to.as_str());
+            }
+        }
+
+        None
+    }
+}
+
+/// Prices a conversion path using last-trade prices keyed by product id.
+pub(crate) fn price_path(
+    amount: Decimal,
+    from: &str,
+    to: &str,
+    path: Vec<Leg>,
+    prices: &HashMap<String, Decimal>,
+) -> Result<Conversion, anyhow::Error> {
+    let mut rate = Decimal::ONE;
+    let mut hops = Vec::with_capacity(path.len());
+    for leg in path {
+        let price = *prices.get(&leg.product_id)
+            .ok_or_else(|| anyhow!("missing price for {}", leg.product_id))?;
+        if price.is_zero() {
+            return Err(anyhow!("zero price for {}", leg.product_id));
+        }
+        let hop_rate = if leg.inverted { Decimal::ONE / price } else { price };
+        rate *= hop_rate;
+        hops.push(Hop { leg, rate: hop_rate });
+    }
+
+    Ok(Conversion {
+        from: from.to_uppercase(),
+        to: to.to_uppercase(),
+        amount,
+        converted: amount * rate,
+        rate,
+        hops,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(base: &str, quote: &str) -> Product {
+        Product {
+            id: format!("{}-{}", base, quote),
+            base_currency: base.to_owned(),
+            quote_currency: quote.to_owned(),
+            quote_increment: Decimal::new(1, 2),
+            base_increment: Decimal::new(1, 8),
+            display_name: format!("{}/{}", base, quote),
+            base_min_size: None,
+            base_max_size: None,
+            min_market_funds: None,
+            max_market_funds: None,
+            status: ProductStatus::Online,
+            status_message: String::new(),
+            post_only: false,
+            limit_only: false,
+            cancel_only: false,
+            trading_disabled: false,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_path_through_intermediate_currency() {
+        let graph = ProductGraph::new(&[product("ATOM", "BTC"), product("BTC", "USD"), product("ETH", "USD")]);
+        let path = graph.path("atom", "usd").unwrap();
+        let products: Vec<&str> = path.iter().map(|leg| leg.product_id.as_str()).collect();
+        assert_eq!(products, vec!["ATOM-BTC", "BTC-USD"]);
+        assert!(!path[0].inverted);
+
+        let reverse = graph.path("USD", "ATOM").unwrap();
+        assert!(reverse.iter().all(|leg| leg.inverted));
+        assert!(graph.path("ATOM", "DOGE").is_none());
+        assert!(graph.path("BTC", "BTC").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_price_path() {
+        let graph = ProductGraph::new(&[product("ATOM", "BTC"), product("BTC", "USD")]);
+        let prices = HashMap::from([
+            ("ATOM-BTC".to_owned(), Decimal::new(5, 4)),
+            ("BTC-USD".to_owned(), Decimal::from(20_000)),
+        ]);
+
+        let conversion = price_path(Decimal::from(10), "ATOM", "USD", graph.path("ATOM", "USD").unwrap(), &prices).unwrap();
+        assert_eq!(conversion.rate, Decimal::from(10));
+        assert_eq!(conversion.converted, Decimal::from(100));
+        assert_eq!(conversion.hops.len(), 2);
+
+        let conversion = price_path(Decimal::from(100), "USD", "ATOM", graph.path("USD", "ATOM").unwrap(), &prices).unwrap();
+        assert_eq!(conversion.converted, Decimal::from(10));
+    }
+}
@@ -0,0 +1,128 @@
+//! Currency codes, with the most traded ones as enum variants.
+//!
+//! The list of variants is generated from 'get_currencies' with 'cb currency-constants'. Paste
+//! its output into the known_currencies invocation below to refresh it. Codes not in the list
+//! still parse, as KnownCurrency::Other, so new listings never break deserialization.
+
+// std
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+// external
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+macro_rules! known_currencies {
+    ($($variant:ident),* $(,)?) => {
+        /// Currency code such as 'BTC' or 'USD'. Parsing is case-insensitive and never fails.
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum KnownCurrency {
+            $($variant,)*
+            /// Any currency not in the generated list. Only parsing builds it, so a known code
+            /// always becomes its own variant and equal codes always compare equal.
+            Other(OtherCurrency),
+        }
+
+        impl KnownCurrency {
+            /// Every known currency, in alphabetical order.
+            pub const ALL: &'static [KnownCurrency] = &[$(KnownCurrency::$variant,)*];
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(KnownCurrency::$variant => stringify!($variant),)*
+                    KnownCurrency::Other(code) => code.as_str(),
+                }
+            }
+        }
+
+        impl FromStr for KnownCurrency {
+            type Err = Infallible;
+
+            fn from_str(code: &str) -> Result<Self, Self::Err> {
+                let code = code.trim().to_ascii_uppercase();
+                Ok(match code.as_str() {
+                    $(stringify!($variant) => KnownCurrency::$variant,)*
+                    _ => KnownCurrency::Other(OtherCurrency(code)),
+                })
+            }
+        }
+    };
+}
+
+/// Uppercased code of a currency without a KnownCurrency variant.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OtherCurrency(String);
+
+impl OtherCurrency {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+known_currencies! {
+    AAVE, ADA, ALGO, ATOM, AVAX, BCH, BTC, COMP, DAI, DOGE, DOT, ETC, ETH, EUR, GBP, LINK, LTC,
+    MATIC, MKR, SHIB, SOL, UNI, USD, USDC, USDT, XLM, XTZ,
+}
+
+impl KnownCurrency {
+    /// Whether this is one of the generated variants rather than Other.
+    pub fn is_known(&self) -> bool {
+        !matches!(self, KnownCurrency::Other(_))
+    }
+}
+
+impl From<&str> for KnownCurrency {
+    fn from(code: &str) -> Self {
+        match code.parse() {
+            Ok(currency) => currency,
+            Err(never) => match never {},
+        }
+    }
+}
+
+impl fmt::Display for KnownCurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for KnownCurrency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for KnownCurrency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let code = String::deserialize(deserializer)?;
+        Ok(KnownCurrency::from(code.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_currencies() {
+        assert_eq!(KnownCurrency::from("usdc"), KnownCurrency::USDC);
+        assert_eq!(KnownCurrency::from("NEWCOIN").as_str(), "NEWCOIN");
+        assert_eq!(serde_json::to_string(&KnownCurrency::ETH).unwrap(), r#""ETH""#);
+        assert!(KnownCurrency::ALL.windows(2).all(|pair| pair[0].as_str() < pair[1].as_str()));
+
+        // known codes never end up in Other, so equal codes are equal values with equal hashes
+        assert_eq!(KnownCurrency::from(" btc "), KnownCurrency::BTC);
+        let balances = std::collections::HashMap::from([(KnownCurrency::from("btc"), 1)]);
+        assert_eq!(balances.get(&KnownCurrency::BTC), Some(&1));
+        assert_eq!(KnownCurrency::from("newcoin"), KnownCurrency::from("NewCoin"));
+
+        // every currency in the recorded list should have a variant
+        let currencies: Vec<serde_json::Value> = serde_json::from_str(include_str!("../tests/fixtures/currencies.json")).unwrap();
+        for currency in currencies {
+            let parsed: KnownCurrency = serde_json::from_value(currency["id"].clone()).unwrap();
+            assert!(parsed.is_known(), "{} is missing from known_currencies", parsed);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! A common interface over live and recorded market data, so strategy and research code can
+//! run unchanged against either.
+
+// std
+use std::collections::HashMap;
+use std::future::Future;
+// external
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+// crate
+use crate::{CoinbasePublicClient, Granularity};
+use crate::models::{Candle, Trade};
+use crate::product_id::ProductId;
+use crate::replay::FullMessage;
+
+/// Number of trades Coinbase returns per page.
+const TRADES_PER_PAGE: usize = 100;
+
+/// Market data for a product over a time range. Ranges include both 'start' and 'end', and
+/// results are ordered oldest first.
+pub trait MarketDataSource {
+    /// Candles whose start time is within the range.
+    fn candles(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity
+    ) -> impl Future<Output = Result<Vec<Candle>, anyhow::Error>> + Send;
+
+    /// Trades within the range, ordered by trade_id.
+    fn trades(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>
+    ) -> impl Future<Output = Result<Vec<Trade>, anyhow::Error>> + Send;
+
+    /// Full channel messages within the range, in sequence order.
+    fn book_events(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>
+    ) -> impl Future<Output = Result<Vec<FullMessage>, anyhow::Error>> + Send;
+}
+
+/// Live data from the REST API. Candle ranges longer than Coinbase's 300 candle limit are
+/// fetched in several requests. Trades are paged backwards from the latest trade until 'start'
+/// is reached, which takes one request per 100 trades, so keep trade ranges recent and short.
+/// Book events are not available over REST.
+impl MarketDataSource for CoinbasePublicClient {
+    async fn candles(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity
+    ) -> Result<Vec<Candle>, anyhow::Error> {
+        self.get_candles_chunked(product_id, start, end, granularity).await
+    }
+
+    async fn trades(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>
+    ) -> Result<Vec<Trade>, anyhow::Error> {
+        let endpoint = format!("/products/{}/trades", product_id);
+        let mut trades: Vec<Trade> = Vec::new();
+        let mut cursor: Option<u64> = None;
+        loop {
+            // 'after' pages towards older trades, below the given trade id
+            let params = cursor.map(|id| vec![("after".to_owned(), id.to_string())]);
+            let page: Vec<Trade> = self.get_typed(&endpoint, params).await?;
+            let reached_start = page.len() < TRADES_PER_PAGE || page.iter().any(|trade| trade.time < start);
+            cursor = page.iter().map(|trade| trade.trade_id).min();
+            trades.extend(page.into_iter().filter(|trade| trade.time >= start && trade.time <= end));
+            if reached_start || cursor.is_none() {
+                break;
+            }
+        }
+        trades.sort_by_key(|trade| trade.trade_id);
+        trades.dedup_by_key(|trade| trade.trade_id);
+        Ok(trades)
+    }
+
+    async fn book_events(
+        &self,
+        product_id: &ProductId,
+        _start: DateTime<Utc>,
+        _end: DateTime<Utc>
+    ) -> Result<Vec<FullMessage>, anyhow::Error> {
+        bail!("book events for {} are not available over REST, use recorded data instead", product_id)
+    }
+}
+
+/// Market data recorded earlier, such as candles and trades saved with serde or full channel
+/// messages read with replay::read_messages, held in memory per product.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedMarketData {
+    candles: HashMap<(ProductId, Granularity), Vec<Candle>>,
+    trades: HashMap<ProductId, Vec<Trade>>,
+    book_events: HashMap<ProductId, Vec<FullMessage>>,
+}
+
+impl RecordedMarketData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds candles of one granularity for a product, in any order.
+    pub fn with_candles(mut self, product_id: ProductId, granularity: Granularity, candles: Vec<Candle>) -> Self {
+        let recorded = self.candles.entry((product_id, granularity)).or_default();
+        recorded.extend(candles);
+        recorded.sort_by_key(|candle| candle.time);
+        recorded.dedup_by_key(|candle| candle.time);
+        self
+    }
+
+    /// Adds trades for a product, in any order.
+    pub fn with_trades(mut self, product_id: ProductId, trades: Vec<Trade>) -> Self {
+        let recorded = self.trades.entry(product_id).or_default();
+        recorded.extend(trades);
+        recorded.sort_by_key(|trade| trade.trade_id);
+        recorded.dedup_by_key(|trade| trade.trade_id);
+        self
+    }
+
+    /// Adds full channel messages for a product, in any order. Messages without a sequence
+    /// number, such as subscriptions, are dropped.
+    pub fn with_book_events(mut self, product_id: ProductId, messages: Vec<FullMessage>) -> Self {
+        let recorded = self.book_events.entry(product_id).or_default();
+        recorded.extend(messages.into_iter().filter(|message| message.sequence().is_some()));
+        recorded.sort_by_key(FullMessage::sequence);
+        recorded.dedup_by_key(|message| message.sequence());
+        self
+    }
+}
+
+/// Recorded data answers from memory. Products or granularities with nothing recorded fail,
+/// rather than returning an empty range that could be mistaken for a quiet market.
+impl MarketDataSource for RecordedMarketData {
+    async fn candles(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity
+    ) -> Result<Vec<Candle>, anyhow::Error> {
+        let Some(candles) = self.candles.get(&(product_id.clone(), granularity)) else {
+            bail!("no {:?} candles recorded for {}", granularity, product_id);
+        };
+        Ok(candles.iter().filter(|candle| candle.time >= start && candle.time <= end).cloned().collect())
+    }
+
+    async fn trades(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>
+    ) -> Result<Vec<Trade>, anyhow::Error> {
+        let Some(trades) = self.trades.get(product_id) else {
+            bail!("no trades recorded for {}", product_id);
+        };
+        Ok(trades.iter().filter(|trade| trade.time >= start && trade.time <= end).cloned().collect())
+    }
+
+    async fn book_events(
+        &self,
+        product_id: &ProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>
+    ) -> Result<Vec<FullMessage>, anyhow::Error> {
+        let Some(messages) = self.book_events.get(product_id) else {
+            bail!("no book events recorded for {}", product_id);
+        };
+        Ok(messages.iter()
+            .filter(|message| message.time().is_some_and(|time| time >= start && time <= end))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CannedResponses;
+
+    fn time(hour: u32) -> DateTime<Utc> {
+        format!("2022-10-10T{:02}:00:00Z", hour).parse().unwrap()
+    }
+
+    /// Strategy code only sees the trait.
+    async fn closes<S: MarketDataSource>(source: &S, product_id: &ProductId) -> Vec<String> {
+        let candles = source.candles(product_id, time(10), time(11), Granularity::Hour1).await.unwrap();
+        candles.iter().map(|candle| candle.close.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_live_and_recorded_sources_agree() {
+        let product_id: ProductId = "ETH-USD".parse().unwrap();
+        let json = "[[1665399600, 1, 4, 2, 3, 10], [1665396000, 1, 4, 2, 2.5, 10]]";
+        let client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new().with("/products/ETH-USD/candles", json))
+            .build();
+        let recorded = RecordedMarketData::new()
+            .with_candles(product_id.clone(), Granularity::Hour1, serde_json::from_str(json).unwrap());
+
+        assert_eq!(closes(&client, &product_id).await, ["2.5", "3"]);
+        assert_eq!(closes(&recorded, &product_id).await, ["2.5", "3"]);
+        assert!(recorded.trades(&product_id, time(10), time(11)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_live_trades_page_back_to_start() {
+        let page = |first_id: u64, first_hour: u32| {
+            let trades: Vec<String> = (0..TRADES_PER_PAGE as u64).map(|i| format!(
+                r#"{{"trade_id": {}, "time": "{}", "price": "1", "size": "1", "side": "buy"}}"#,
+                first_id - i, time(first_hour) - chrono::Duration::seconds(i as i64)
+            )).collect();
+            format!("[{}]", trades.join(","))
+        };
+        let client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new()
+                .with("/products/ETH-USD/trades", page(300, 12))
+                .with("/products/ETH-USD/trades?after=201", page(200, 11))
+                .with("/products/ETH-USD/trades?after=101", page(100, 10)))
+            .build();
+
+        let trades = client.trades(&"ETH-USD".parse().unwrap(), time(10), time(11)).await.unwrap();
+        assert_eq!(trades.first().map(|trade| trade.trade_id), Some(100));
+        assert_eq!(trades.last().map(|trade| trade.trade_id), Some(200));
+        assert_eq!(trades.len(), 101);
+    }
+}
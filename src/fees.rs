@@ -0,0 +1,137 @@
+//! Maker/taker fee tiers and fee estimates.
+
+// external
+use anyhow::bail;
+use rust_decimal::Decimal;
+
+/// Whether an order added liquidity to the book (maker) or removed it (taker).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+/// A single fee tier. Applies to 30-day USD volume at or above 'min_volume'.
+/// Rates are fractions, i.e. 0.006 means 0.6%.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeTier {
+    pub min_volume: Decimal,
+    pub maker_rate: Decimal,
+    pub taker_rate: Decimal,
+}
+
+impl FeeTier {
+    pub fn rate(&self, liquidity: Liquidity) -> Decimal {
+        match liquidity {
+            Liquidity::Maker => self.maker_rate,
+            Liquidity::Taker => self.taker_rate,
+        }
+    }
+
+    /// Fee charged on an order of the given notional value, in the quote currency.
+    pub fn fee(&self, liquidity: Liquidity, notional: Decimal) -> Decimal {
+        notional * self.rate(liquidity)
+    }
+}
+
+/// Fee schedule made of tiers sorted by 30-day volume.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeSchedule {
+    tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    /// Builds a custom schedule. Tiers may be given in any order, but one of them must
+    /// start at zero volume.
+    pub fn new(mut tiers: Vec<FeeTier>) -> Result<Self, anyhow::Error> {
+        tiers.sort_by_key(|tier| tier.min_volume);
+        match tiers.first() {
+            Some(tier) if tier.min_volume.is_zero() => Ok(Self { tiers }),
+            _ => bail!("fee schedule must contain a tier starting at zero volume"),
+        }
+    }
+
+    /// Coinbase Pro's published schedule at the time of writing. Coinbase revises this
+    /// occasionally; use FeeSchedule::new if it has changed, or read your actual rates from
+    /// the authenticated fees endpoint.
+    pub fn coinbase_pro() -> Self {
+        let tier = |min_volume: i64, maker_bps: i64, taker_bps: i64| FeeTier {
+            min_volume: Decimal::from(min_volume),
+            maker_rate: Decimal::new(maker_bps, 4),
+            taker_rate: Decimal::new(taker_bps, 4),
+        };
+        Self {
+            tiers: vec![
+                tier(0, 40, 60),
+                tier(10_000, 25, 40),
+                tier(50_000, 15, 25),
+                tier(100_000, 10, 20),
+                tier(1_000_000, 8, 18),
+                tier(15_000_000, 6, 16),
+                tier(75_000_000, 3, 12),
+                tier(250_000_000, 0, 8),
+                tier(400_000_000, 0, 5),
+            ],
+        }
+    }
+
+    pub fn tiers(&self) -> &[FeeTier] {
+        &self.tiers
+    }
+
+    /// Returns the tier that applies to the given 30-day USD volume.
+    pub fn tier_for(&self, volume_30day: Decimal) -> &FeeTier {
+        self.tiers.iter()
+            .rev()
+            .find(|tier| volume_30day >= tier.min_volume)
+            .unwrap_or(&self.tiers[0])
+    }
+
+    /// Estimates the fee, in the quote currency, for a hypothetical order.
+    ///
+    /// # Arguments
+    ///
+    /// * 'volume_30day' - trailing 30-day USD volume that determines the tier.
+    /// * 'liquidity' - whether the order is expected to fill as maker or taker.
+    /// * 'price' - expected fill price.
+    /// * 'size' - order size in the base currency.
+    pub fn estimate(&self, volume_30day: Decimal, liquidity: Liquidity, price: Decimal, size: Decimal) -> Decimal {
+        self.tier_for(volume_30day).fee(liquidity, price * size)
+    }
+}
+
+impl Default for FeeSchedule {
+    fn default() -> Self {
+        FeeSchedule::coinbase_pro()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tier_lookup() {
+        let schedule = FeeSchedule::coinbase_pro();
+        assert_eq!(schedule.tier_for(Decimal::ZERO).taker_rate, Decimal::new(60, 4));
+        assert_eq!(schedule.tier_for(Decimal::from(9_999)).maker_rate, Decimal::new(40, 4));
+        assert_eq!(schedule.tier_for(Decimal::from(10_000)).maker_rate, Decimal::new(25, 4));
+        assert_eq!(schedule.tier_for(Decimal::from(500_000_000)).taker_rate, Decimal::new(5, 4));
+    }
+
+    #[test]
+    fn test_estimate() {
+        let schedule = FeeSchedule::default();
+        // $2,000 notional at the 0.20% taker rate
+        let fee = schedule.estimate(Decimal::from(250_000), Liquidity::Taker, Decimal::from(1000), Decimal::from(2));
+        assert_eq!(fee, Decimal::from(4));
+    }
+
+    #[test]
+    fn test_custom_schedule_requires_base_tier() {
+        let tier = FeeTier { min_volume: Decimal::from(100), maker_rate: Decimal::ZERO, taker_rate: Decimal::ZERO };
+        assert!(FeeSchedule::new(vec![tier.clone()]).is_err());
+        let base = FeeTier { min_volume: Decimal::ZERO, ..tier.clone() };
+        assert!(FeeSchedule::new(vec![tier, base]).is_ok());
+    }
+}
@@ -0,0 +1,341 @@
+//! Detection of Coinbase outages from the client's own requests and a periodic heartbeat
+//! request to the time endpoint.
+//!
+//! Every client keeps a log of its recent request outcomes, summarized by request_stats.
+//! HealthMonitor combines those with its heartbeats into a single Degraded, Down or Recovered
+//! signal. There is no websocket connection, so heartbeat messages and the status channel
+//! aren't used.
+
+// std
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+// external
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::time::MissedTickBehavior;
+// crate
+use crate::CoinbasePublicClient;
+use crate::background::Background;
+
+/// Most request outcomes a client remembers.
+const REQUEST_LOG_CAPACITY: usize = 1000;
+
+/// Number of events buffered for each subscriber before the oldest are dropped.
+const HEALTH_CHANNEL_CAPACITY: usize = 64;
+
+/// Error returned when Coinbase responds with a 5xx status. Retrieve it with anyhow's
+/// downcast_ref.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerError {
+    pub status: u16,
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "coinbase responded with server error {}", self.status)
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+/// Outcome of a single request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Success,
+    ServerError,
+    /// Any other failure, such as a timeout or connection error.
+    Failed,
+}
+
+/// Recent request outcomes of a client.
+#[derive(Debug, Default)]
+pub(crate) struct RequestLog {
+    outcomes: Mutex<VecDeque<(DateTime<Utc>, Outcome)>>,
+}
+
+impl RequestLog {
+    pub(crate) fn record<T>(&self, result: &Result<T, anyhow::Error>) {
+        let outcome = match result {
+            Ok(_) => Outcome::Success,
+            Err(err) if err.downcast_ref::<ServerError>().is_some() => Outcome::ServerError,
+            Err(_) => Outcome::Failed,
+        };
+        let mut outcomes = self.outcomes.lock().unwrap();
+        if outcomes.len() == REQUEST_LOG_CAPACITY {
+            outcomes.pop_front();
+        }
+        outcomes.push_back((Utc::now(), outcome));
+    }
+
+    pub(crate) fn stats(&self, since: DateTime<Utc>) -> RequestStats {
+        let outcomes = self.outcomes.lock().unwrap();
+        let mut stats = RequestStats {
+            last_success: outcomes.iter().rev()
+                .find(|(_, outcome)| *outcome == Outcome::Success)
+                .map(|(time, _)| *time),
+            ..Default::default()
+        };
+        for (_, outcome) in outcomes.iter().filter(|(time, _)| *time >= since) {
+            stats.requests += 1;
+            match outcome {
+                Outcome::Success => {},
+                Outcome::ServerError => {
+                    stats.failures += 1;
+                    stats.server_errors += 1;
+                },
+                Outcome::Failed => stats.failures += 1,
+            }
+        }
+        stats
+    }
+}
+
+/// Summary of a client's recent requests, from request_stats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestStats {
+    pub requests: usize,
+    /// Requests that failed for any reason, including server errors.
+    pub failures: usize,
+    /// Requests answered with a 5xx status.
+    pub server_errors: usize,
+    /// Time of the last successful request, even if outside the window.
+    pub last_success: Option<DateTime<Utc>>,
+}
+
+impl RequestStats {
+    /// Fraction of requests that failed, or None if there were none.
+    pub fn error_rate(&self) -> Option<f64> {
+        (self.requests > 0).then(|| self.failures as f64 / self.requests as f64)
+    }
+}
+
+/// Result of CoinbasePublicClient::health_check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    /// Local time the request was sent.
+    pub checked_at: DateTime<Utc>,
+    /// Round trip time of the request, not counting any wait for the rate limiter.
+    pub latency: Duration,
+    /// Time reported by the server, if the response parsed.
+    pub server_time: Option<DateTime<Utc>>,
+    /// Why the check failed, if it did.
+    pub error: Option<String>,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Overall availability of Coinbase as seen by this client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HealthStatus {
+    #[default]
+    Healthy,
+    Degraded,
+    Down,
+}
+
+/// Change of HealthStatus, with the reason for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthEvent {
+    Degraded { reason: String },
+    Down { reason: String },
+    /// Back to healthy after being degraded or down since 'since'.
+    Recovered { since: DateTime<Utc> },
+}
+
+/// When HealthTracker considers Coinbase degraded or down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthThresholds {
+    /// Heartbeats slower than this count as degraded.
+    pub slow_heartbeat: Duration,
+    /// Fraction of failed requests within 'window' at which Coinbase counts as degraded.
+    pub degraded_error_rate: f64,
+    /// Consecutive failed heartbeats after which Coinbase counts as down.
+    pub down_after: u32,
+    /// How far back request outcomes are considered.
+    pub window: Duration,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            slow_heartbeat: Duration::from_secs(2),
+            degraded_error_rate: 0.25,
+            down_after: 3,
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Turns heartbeat results and request stats into health events, without doing any requests
+/// itself. HealthMonitor runs one in the background.
+#[derive(Debug, Clone, Default)]
+pub struct HealthTracker {
+    thresholds: HealthThresholds,
+    status: HealthStatus,
+    unhealthy_since: Option<DateTime<Utc>>,
+    failed_heartbeats: u32,
+}
+
+impl HealthTracker {
+    pub fn new(thresholds: HealthThresholds) -> Self {
+        Self { thresholds, ..Default::default() }
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        self.status
+    }
+
+    /// Updates the status from the latest heartbeat and returns an event if it changed.
+    ///
+    /// # Arguments
+    ///
+    /// * 'heartbeat' - result of a health check.
+    /// * 'stats' - request stats over the thresholds' window.
+    pub fn update(&mut self, heartbeat: &HealthReport, stats: &RequestStats) -> Option<HealthEvent> {
+        match heartbeat.error {
+            None => self.failed_heartbeats = 0,
+            Some(_) => self.failed_heartbeats += 1,
+        }
+        let (status, reason) = match &heartbeat.error {
+            _ if self.failed_heartbeats >= self.thresholds.down_after => {
+                (HealthStatus::Down, format!("{} heartbeats in a row failed", self.failed_heartbeats))
+            },
+            Some(err) => (HealthStatus::Degraded, format!("heartbeat failed: {}", err)),
+            None if heartbeat.latency > self.thresholds.slow_heartbeat => {
+                (HealthStatus::Degraded, format!("heartbeat took {}ms", heartbeat.latency.as_millis()))
+            },
+            _ if stats.error_rate().is_some_and(|rate| rate >= self.thresholds.degraded_error_rate) => {
+                (HealthStatus::Degraded, format!(
+                    "{} of {} requests failed, {} with server errors", stats.failures, stats.requests, stats.server_errors
+                ))
+            },
+            _ => (HealthStatus::Healthy, String::new()),
+        };
+
+        if status == self.status {
+            return None;
+        }
+        self.status = status;
+        match status {
+            HealthStatus::Healthy => Some(HealthEvent::Recovered { since: self.unhealthy_since.take()? }),
+            HealthStatus::Degraded => {
+                self.unhealthy_since.get_or_insert_with(Utc::now);
+                Some(HealthEvent::Degraded { reason })
+            },
+            HealthStatus::Down => {
+                self.unhealthy_since.get_or_insert_with(Utc::now);
+                Some(HealthEvent::Down { reason })
+            },
+        }
+    }
+}
+
+/// Runs health_check at a fixed interval and emits an event
+/// whenever Coinbase becomes degraded, goes down or recovers, judging by the heartbeats and all
+/// other requests made through the same client.
+///
+/// Heartbeats go through the client's rate limiter, and stop when the monitor is dropped.
+/// Heartbeats missed during a stall are skipped, so they don't arrive in a burst afterwards.
+#[derive(Debug)]
+pub struct HealthMonitor {
+    status: Arc<Mutex<HealthStatus>>,
+    events: Background<HealthEvent>,
+}
+
+impl HealthMonitor {
+    /// Starts monitoring on the current tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - shared client, whose other requests count towards the error rate.
+    /// * 'interval' - time between heartbeats.
+    /// * 'thresholds' - when to report degraded or down. HealthThresholds::default() suits
+    ///   most uses.
+    pub fn spawn(client: Arc<CoinbasePublicClient>, interval: Duration, thresholds: HealthThresholds) -> Self {
+        let status = Arc::new(Mutex::new(HealthStatus::Healthy));
+        let shared_status = status.clone();
+
+        let events = Background::spawn(HEALTH_CHANNEL_CAPACITY, |events| async move {
+            let mut tracker = HealthTracker::new(thresholds);
+            let window = chrono::Duration::from_std(thresholds.window).unwrap_or(chrono::Duration::MAX);
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticks.tick().await;
+                let heartbeat = client.health_check().await;
+                let stats = client.request_stats(window);
+                if let Some(event) = tracker.update(&heartbeat, &stats) {
+                    *shared_status.lock().unwrap() = tracker.status();
+                    events.publish(event);
+                }
+            }
+        });
+
+        Self { status, events }
+    }
+
+    /// Receives health events, keeping up to HEALTH_CHANNEL_CAPACITY unread ones per
+    /// subscriber.
+    pub fn subscribe(&self) -> broadcast::Receiver<HealthEvent> {
+        self.events.subscribe()
+    }
+
+    /// Status as of the last heartbeat.
+    pub fn status(&self) -> HealthStatus {
+        *self.status.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat(latency_ms: u64, error: Option<&str>) -> HealthReport {
+        HealthReport {
+            checked_at: Utc::now(),
+            latency: Duration::from_millis(latency_ms),
+            server_time: error.is_none().then(Utc::now),
+            error: error.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_health_transitions() {
+        let mut tracker = HealthTracker::new(HealthThresholds { down_after: 2, ..Default::default() });
+        let quiet = RequestStats::default();
+        let (fast, slow, timeout) = (heartbeat(50, None), heartbeat(5000, None), heartbeat(30000, Some("timed out")));
+
+        assert_eq!(tracker.update(&fast, &quiet), None);
+        let failing = RequestStats { requests: 10, failures: 5, server_errors: 4, last_success: None };
+        assert_eq!(tracker.update(&fast, &failing), Some(HealthEvent::Degraded {
+            reason: "5 of 10 requests failed, 4 with server errors".to_owned(),
+        }));
+        assert_eq!(tracker.update(&timeout, &failing), None);
+        assert!(matches!(tracker.update(&timeout, &failing), Some(HealthEvent::Down { .. })));
+        assert_eq!(tracker.status(), HealthStatus::Down);
+
+        assert!(matches!(tracker.update(&slow, &quiet), Some(HealthEvent::Degraded { .. })));
+        assert!(matches!(tracker.update(&fast, &quiet), Some(HealthEvent::Recovered { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_monitor_reports_down() {
+        // no canned response for the time endpoint, so every heartbeat fails
+        let client = Arc::new(CoinbasePublicClient::builder().offline(crate::testing::CannedResponses::new()).build());
+        let monitor = HealthMonitor::spawn(client.clone(), Duration::from_millis(5), HealthThresholds::default());
+        let mut events = monitor.subscribe();
+
+        assert!(matches!(events.recv().await.unwrap(), HealthEvent::Degraded { .. }));
+        assert!(matches!(events.recv().await.unwrap(), HealthEvent::Down { .. }));
+        assert_eq!(monitor.status(), HealthStatus::Down);
+        let stats = client.request_stats(chrono::Duration::minutes(1));
+        assert!(stats.failures >= 3 && stats.last_success.is_none());
+    }
+}
@@ -0,0 +1,226 @@
+//! Clients sending requests over the network, one per http backend feature.
+
+// std
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+// external
+use anyhow::Context;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use url::Url;
+// crate
+use crate::APP_USER_AGENT;
+use crate::health::ServerError;
+use crate::transport::HttpTransport;
+
+/// Connection options from the client builder. The http2 options only apply to reqwest.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(any(feature = "reqwest", feature = "hyper")), allow(dead_code))]
+pub(crate) struct HttpOptions {
+    pub(crate) pool_idle_timeout: Duration,
+    pub(crate) tcp_keepalive: Option<Duration>,
+    #[cfg_attr(feature = "hyper", allow(dead_code))]
+    pub(crate) http2_prior_knowledge: bool,
+    #[cfg_attr(feature = "hyper", allow(dead_code))]
+    pub(crate) http2_adaptive_window: bool,
+}
+
+#[cfg(feature = "hyper")]
+type HyperClient = hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
+
+/// Client sending requests over the network.
+pub(crate) enum HttpClient {
+    #[cfg(all(feature = "reqwest", not(feature = "hyper")))]
+    Reqwest(reqwest::Client),
+    /// Client built elsewhere with its own middleware stack.
+    #[cfg(feature = "middleware")]
+    Middleware(reqwest_middleware::ClientWithMiddleware),
+    #[cfg(feature = "hyper")]
+    Hyper(HyperClient),
+    /// Transport implemented outside the crate.
+    Custom(Arc<dyn HttpTransport>),
+}
+
+impl fmt::Debug for HttpClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(all(feature = "reqwest", not(feature = "hyper")))]
+            HttpClient::Reqwest(http_client) => f.debug_tuple("Reqwest").field(http_client).finish(),
+            #[cfg(feature = "middleware")]
+            HttpClient::Middleware(http_client) => f.debug_tuple("Middleware").field(http_client).finish(),
+            #[cfg(feature = "hyper")]
+            HttpClient::Hyper(http_client) => f.debug_tuple("Hyper").field(http_client).finish(),
+            HttpClient::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl HttpClient {
+    /// Builds the client of the enabled backend, preferring hyper if both are enabled.
+    #[cfg_attr(not(any(feature = "reqwest", feature = "hyper")), allow(unused_variables))]
+    pub(crate) fn new(options: &HttpOptions) -> Self {
+        #[cfg(feature = "hyper")]
+        {
+            let mut http = hyper::client::HttpConnector::new();
+            http.enforce_http(false);
+            http.set_keepalive(options.tcp_keepalive);
+            let https = hyper_rustls::HttpsConnectorBuilder::new()
+                .with_webpki_roots()
+                .https_or_http()
+                .enable_http1()
+                .wrap_connector(http);
+            HttpClient::Hyper(hyper::Client::builder()
+                .pool_idle_timeout(options.pool_idle_timeout)
+                .build(https))
+        }
+        #[cfg(all(feature = "reqwest", not(feature = "hyper")))]
+        {
+            let mut builder = reqwest::Client::builder()
+                .user_agent(APP_USER_AGENT)
+                .pool_idle_timeout(options.pool_idle_timeout)
+                .tcp_keepalive(options.tcp_keepalive)
+                .http2_adaptive_window(options.http2_adaptive_window);
+            if options.http2_prior_knowledge {
+                builder = builder.http2_prior_knowledge();
+            }
+            HttpClient::Reqwest(builder.build().unwrap_or_else(|_| reqwest::Client::new()))
+        }
+        #[cfg(not(any(feature = "reqwest", feature = "hyper")))]
+        HttpClient::Custom(Arc::new(crate::transport::NoTransport))
+    }
+
+    /// Sends get message, checks the response status and returns the body as text.
+    pub(crate) async fn get(&self, url: &Url, timeout: Duration) -> Result<String, anyhow::Error> {
+        let mut body = Vec::new();
+        self.get_into(url, timeout, &mut body).await?;
+        String::from_utf8(body).context("failure while decoding response to text")
+    }
+
+    /// Sends get message, checks the response status and copies the body into 'writer' chunk
+    /// by chunk. Returns the number of bytes written. 'timeout' covers reading the body too.
+    pub(crate) async fn get_into<W: AsyncWrite + Unpin>(
+        &self,
+        url: &Url,
+        timeout: Duration,
+        writer: &mut W
+    ) -> Result<u64, anyhow::Error> {
+        match self {
+            #[cfg(all(feature = "reqwest", not(feature = "hyper")))]
+            HttpClient::Reqwest(http_client) => {
+                let response = http_client
+                    .get(url.clone())
+                    .timeout(timeout)
+                    .send().await
+                    .context("failure while sending request")?;
+                copy_reqwest_body(response, writer).await
+            },
+            #[cfg(feature = "middleware")]
+            HttpClient::Middleware(http_client) => {
+                let response = http_client
+                    .get(url.clone())
+                    .timeout(timeout)
+                    .send().await
+                    .context("failure while sending request")?;
+                copy_reqwest_body(response, writer).await
+            },
+            #[cfg(feature = "hyper")]
+            HttpClient::Hyper(http_client) => tokio::time::timeout(timeout, hyper_get_into(http_client, url, writer)).await
+                .map_err(|_| anyhow::anyhow!("request timed out after {:?}", timeout))?,
+            HttpClient::Custom(transport) => {
+                let request = http::Request::get(url.as_str())
+                    .header(http::header::USER_AGENT, APP_USER_AGENT)
+                    .body(())
+                    .context("failed to build request")?;
+                let response = tokio::time::timeout(timeout, transport.send(request)).await
+                    .map_err(|_| anyhow::anyhow!("request timed out after {:?}", timeout))?
+                    .context("failure while sending request")?;
+                check_status(response.status().as_u16())?;
+                let body = response.into_body();
+                writer.write_all(&body).await.context("failure while writing response")?;
+                Ok(body.len() as u64)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+async fn copy_reqwest_body<W: AsyncWrite + Unpin>(mut response: reqwest::Response, writer: &mut W) -> Result<u64, anyhow::Error> {
+    check_status(response.status().as_u16())?;
+    let mut written = 0;
+    while let Some(chunk) = response.chunk().await.context("failure while reading response")? {
+        writer.write_all(&chunk).await.context("failure while writing response")?;
+        written += chunk.len() as u64;
+    }
+    Ok(written)
+}
+
+#[cfg(feature = "hyper")]
+async fn hyper_get_into<W: AsyncWrite + Unpin>(http_client: &HyperClient, url: &Url, writer: &mut W) -> Result<u64, anyhow::Error> {
+    use hyper::body::HttpBody;
+
+    let request = hyper::Request::get(url.as_str())
+        .header(hyper::header::USER_AGENT, APP_USER_AGENT)
+        .body(hyper::Body::empty())
+        .context("failed to build request")?;
+    let response = http_client.request(request).await.context("failure while sending request")?;
+    check_status(response.status().as_u16())?;
+
+    let mut body = response.into_body();
+    let mut written = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.context("failure while reading response")?;
+        writer.write_all(&chunk).await.context("failure while writing response")?;
+        written += chunk.len() as u64;
+    }
+    Ok(written)
+}
+
+/// Turns 5xx responses into a ServerError. Other error statuses carry a message that
+/// get_typed reports, so they pass through.
+fn check_status(status: u16) -> Result<(), anyhow::Error> {
+    if (500..600).contains(&status) {
+        return Err(ServerError { status }.into());
+    }
+    Ok(())
+}
+
+#[cfg(all(test, any(feature = "reqwest", feature = "hyper")))]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use hyper::{Body, Request, Response, Server, StatusCode};
+    use hyper::service::{make_service_fn, service_fn};
+
+    async fn serve(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let response = match request.uri().path() {
+            "/down" => Response::builder().status(StatusCode::SERVICE_UNAVAILABLE).body(Body::from("unavailable")),
+            _ => Response::builder().body(Body::from(r#"{"iso":"2022-10-10T10:00:00Z","epoch":1665396000.0}"#)),
+        };
+        Ok(response.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_get_from_local_server() {
+        let make_service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(serve)) });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+
+        let http_client = HttpClient::new(&HttpOptions {
+            pool_idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: None,
+            http2_prior_knowledge: false,
+            http2_adaptive_window: false,
+        });
+        let timeout = Duration::from_secs(5);
+
+        let url = Url::parse(&format!("http://{}/time", address)).unwrap();
+        let body = http_client.get(&url, timeout).await.unwrap();
+        assert!(body.contains("1665396000"));
+
+        let url = Url::parse(&format!("http://{}/down", address)).unwrap();
+        let err = http_client.get(&url, timeout).await.unwrap_err();
+        assert_eq!(err.downcast_ref::<ServerError>(), Some(&ServerError { status: 503 }));
+    }
+}
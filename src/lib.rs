@@ -1,27 +1,87 @@
-/// Coinbase Pro REST API public client
+//! Coinbase Pro REST API public client
 
 // std
+use std::collections::HashMap;
 use std::num::NonZeroU32;
-use std::fmt::Debug;
+use std::path::PathBuf;
+use std::fmt::{self, Debug};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 // external
-use reqwest::{Method, Url};
-use reqwest;
 use governor::{
     Quota,
     RateLimiter,
     clock::DefaultClock,
     state::{InMemoryState, NotKeyed}
 };
-use anyhow;
-use anyhow::Context;
-use chrono::{DateTime, Utc};
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use futures::future::try_join_all;
+use rust_decimal::Decimal;
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use url::Url;
+// crate
+use candles::RangeBound;
+use coalesce::InFlight;
+use conversion::{Conversion, ProductGraph};
+use health::{HealthReport, RequestLog, RequestStats};
+use http_client::{HttpClient, HttpOptions};
+use models::{
+    Candle, ErrorMessage, MarketOverview, MarketSnapshot, MarketSort, MarketSummary, OrderBookL2, Product, ProductStatus, Quote,
+    ServerTime, Stats, Ticker
+};
+use product_id::{IntoProductId, ProductId};
+use schema::{DeserializeMode, UnknownFields};
+use scheduler::Scheduler;
+use testing::{CannedResponses, FixtureMode, Fixtures};
+use time_sync::{ClockSkew, TimeSample};
+use transport::HttpTransport;
+
+pub mod alerts;
+pub mod analytics;
+mod background;
+pub mod batch;
+pub mod candles;
+mod coalesce;
+pub mod conversion;
+pub mod currency;
+pub mod data_source;
+pub mod fees;
+pub mod health;
+mod http_client;
+pub mod models;
+pub mod orderbook;
+pub mod poller;
+pub mod positions;
+pub mod prices;
+pub mod product_id;
+pub mod product_watch;
+#[cfg(feature = "products")]
+pub mod products;
+pub mod replay;
+pub mod scheduler;
+pub mod schema;
+#[cfg(feature = "tower")]
+pub mod service;
+pub mod sink;
+pub mod testing;
+pub mod time_sync;
+pub mod trades;
+pub mod transport;
+pub mod value;
+pub mod watcher;
 
 /// Default Constants
 pub(crate) const COINBASE_API_URL: &str = "https://api.pro.coinbase.com";
 pub(crate) const DEFAULT_REQUEST_TIMEOUT: u8 = 30;
 pub(crate) const DEFAULT_RATE_LIMIT: u8 = 3;
 pub(crate) const DEFAULT_BURST_SIZE: u8 = 6;
+pub(crate) const DEFAULT_TIME_SYNC_SAMPLES: u8 = 5;
+pub(crate) const DEFAULT_POOL_IDLE_TIMEOUT: u8 = 90;
+pub(crate) const DEFAULT_OVERVIEW_CONCURRENCY: usize = 6;
 pub(crate) const APP_USER_AGENT: &str = concat!(
     env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")
 );
@@ -30,13 +90,31 @@ pub(crate) const APP_USER_AGENT: &str = concat!(
 #[derive(Debug)]
 pub struct CoinbasePublicClient {
     api_url: &'static str,
-    http_client: reqwest::Client,
+    transport: Transport,
     request_timeout: u8,
     rate_limiter: Option<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>,
+    scheduler: Scheduler,
+    clock_skew: RwLock<Option<ClockSkew>>,
+    in_flight: Option<InFlight>,
+    deserialize_mode: DeserializeMode,
+    unknown_fields: Option<AtomicU64>,
+    request_log: RequestLog,
+}
+
+/// Where the client's requests are sent.
+#[derive(Debug)]
+enum Transport {
+    Http(HttpClient),
+    /// Sends requests over http and saves each response as a fixture.
+    Record(HttpClient, Fixtures),
+    /// Serves saved fixtures without any network access.
+    Replay(Fixtures),
+    /// Serves in-memory responses without any network access.
+    Canned(CannedResponses),
 }
 
 /// Enum representing Coinbase's orderbook options.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OBLevel {
     Level1 = 1,
     Level2 = 2,
@@ -47,12 +125,45 @@ pub enum OBLevel {
 /// as a request parameter.
 impl OBLevel {
     fn param_tuple(&self) -> (String, String) {
-        ("level".to_owned(), (self.to_owned() as u8).to_string())
+        ("level".to_owned(), (*self as u8).to_string())
+    }
+}
+
+impl TryFrom<u8> for OBLevel {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(OBLevel::Level1),
+            2 => Ok(OBLevel::Level2),
+            3 => Ok(OBLevel::Level3),
+            _ => bail!("invalid orderbook level {}, expected 1, 2 or 3", value),
+        }
+    }
+}
+
+/// Parses '1', '2', '3' or 'level1', 'level2', 'level3' (case-insensitive).
+impl FromStr for OBLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.trim().to_lowercase();
+        let digit = lowercase.strip_prefix("level").unwrap_or(&lowercase).trim();
+        digit.parse::<u8>()
+            .map_err(|_| anyhow!("invalid orderbook level '{}', expected 1, 2 or 3", s))
+            .and_then(OBLevel::try_from)
+    }
+}
+
+/// Formats as the level number, which FromStr accepts.
+impl fmt::Display for OBLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", *self as u8)
     }
 }
 
 /// Enum representing Coinbase's accepted candle granularities, in seconds.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Granularity {
     Minute1 = 60,
     Minute5 = 300,
@@ -64,12 +175,97 @@ pub enum Granularity {
 
 impl Granularity {
     fn param_tuple(&self) -> (String, String) {
-        ("granularity".to_owned(), (self.to_owned() as u32).to_string())
+        ("granularity".to_owned(), self.seconds().to_string())
+    }
+
+    /// Candle size in seconds.
+    pub fn seconds(&self) -> u32 {
+        *self as u32
+    }
+
+    /// Every granularity Coinbase accepts, smallest first.
+    pub const ALL: [Granularity; 6] = [
+        Granularity::Minute1,
+        Granularity::Minute5,
+        Granularity::Minute15,
+        Granularity::Hour1,
+        Granularity::Hour6,
+        Granularity::Hour24,
+    ];
+}
+
+/// Converts a candle size in seconds.
+impl TryFrom<u32> for Granularity {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Granularity::ALL.into_iter()
+            .find(|granularity| granularity.seconds() == value)
+            .ok_or_else(|| anyhow!("unsupported granularity of {} seconds", value))
+    }
+}
+
+/// Parses '1m', '5m', '15m', '1h', '6h', '1d' (or '24h'), or a number of seconds such as '3600'.
+impl FromStr for Granularity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "1m" => Ok(Granularity::Minute1),
+            "5m" => Ok(Granularity::Minute5),
+            "15m" => Ok(Granularity::Minute15),
+            "1h" => Ok(Granularity::Hour1),
+            "6h" => Ok(Granularity::Hour6),
+            "1d" | "24h" => Ok(Granularity::Hour24),
+            other => other.parse::<u32>()
+                .map_err(|_| anyhow!("invalid granularity '{}', expected 1m, 5m, 15m, 1h, 6h or 1d", s))
+                .and_then(Granularity::try_from),
+        }
+    }
+}
+
+/// Formats as '1m', '5m', '15m', '1h', '6h' or '1d', which FromStr accepts.
+impl fmt::Display for Granularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Granularity::Minute1 => "1m",
+            Granularity::Minute5 => "5m",
+            Granularity::Minute15 => "15m",
+            Granularity::Hour1 => "1h",
+            Granularity::Hour6 => "6h",
+            Granularity::Hour24 => "1d",
+        };
+        f.write_str(label)
     }
 }
 
 type Params = Vec<(String, String)>;
 
+/// Builds historic rates parameters, rejecting ranges Coinbase would refuse.
+fn candle_params(
+    start_opt: Option<DateTime<Utc>>,
+    end_opt: Option<DateTime<Utc>>,
+    granularity_opt: Option<Granularity>
+) -> Result<Option<Params>, anyhow::Error> {
+    if let (Some(start), Some(end), Some(granularity)) = (start_opt, end_opt, granularity_opt) {
+        candles::validate_range(start, end, granularity)?;
+    }
+
+    let mut params: Params = Vec::new();
+    if let Some(start) = start_opt {
+        params.push(("start".to_owned(), start.to_rfc3339()))
+    }
+    if let Some(end) = end_opt {
+        params.push(("end".to_owned(), end.to_rfc3339()))
+    }
+    if let Some(granularity) = granularity_opt { params.push(granularity.param_tuple()); }
+
+    Ok(match params.is_empty() {
+        true => None,
+        false => Some(params)
+    })
+}
+
 impl CoinbasePublicClient {
     /// Instantiate a new Coinbase public client using default parameters.
     pub fn new() -> Self {
@@ -84,9 +280,9 @@ impl CoinbasePublicClient {
     /// * 'api_url' - API URL . Defaults to const COINBASE_API_URL (https://api.pro.coinbase.com)
     /// * 'request_timeout' - HTTP request timeout (in seconds). Defaults to const DEFAULT_REQUEST_TIMEOUT (30).
     /// * 'rate_limit' - Number of requests per second allowed. Set to zero to disable rate-limiting.
-    /// Defaults to const DEFAULT_RATE_LIMIT (3).
+    ///   Defaults to const DEFAULT_RATE_LIMIT (3).
     /// * 'burst_size' - Number of requests that can be burst when rate-limiting is enabled.
-    /// Defaults to const DEFAULT_BURST_SIZE (6).
+    ///   Defaults to const DEFAULT_BURST_SIZE (6).
     ///
     ///  # Example
     ///             use coinbase_pro_api::CoinbasePublicClient;
@@ -104,71 +300,137 @@ impl CoinbasePublicClient {
     /// Get list of available markets to trade.
     pub async fn get_products(&self) -> Result<String, anyhow::Error> {
         let endpoint = "/products";
-        Ok(self.get_json(endpoint, None).await?)
+        self.get_json(endpoint, None).await
     }
 
     /// Returns information about a single market
     ///
     /// # Arguments
     ///
-    /// * 'product_id' - market identifier formatted as 'BASE-QUOTE', such as 'ETH-USD'.
-    /// String can be lowercase or uppercase.
-    pub async fn get_product(&self, product_id: &str) -> Result<String, anyhow::Error> {
-        let endpoint = "/products/".to_owned() + product_id;
-        Ok(self.get_json(&endpoint, None).await?)
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    pub async fn get_product(&self, product_id: impl IntoProductId) -> Result<String, anyhow::Error> {
+        let endpoint = format!("/products/{}", product_id.into_product_id()?);
+        self.get_json(&endpoint, None).await
     }
 
     /// Returns up to a full (level 3) orderbook from a single market.
     ///
     /// # Arguments
     ///
-    /// * 'product_id' - market identifier formatted as 'BASE-QUOTE', such as 'ETH-USD'.
-    /// String can be lowercase or uppercase.
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
     ///
     /// * 'level' - Level 1 will return the best bid and best ask.
-    /// Level 2 will return the 50 best bid and ask levels, aggregated.
-    /// Level 3 will return the full orderbook, unaggregated.
-    pub async fn get_product_orderbook(&self, product_id: &str, level: OBLevel) -> Result<String, anyhow::Error> {
+    ///   Level 2 will return the 50 best bid and ask levels, aggregated.
+    ///   Level 3 will return the full orderbook, unaggregated.
+    pub async fn get_product_orderbook(&self, product_id: impl IntoProductId, level: OBLevel) -> Result<String, anyhow::Error> {
         let params: Params = vec![level.param_tuple()];
+        let endpoint = format!("/products/{}/book", product_id.into_product_id()?);
+        self.get_json(&endpoint, Some(params)).await
+    }
+
+    /// Fetches order books of several products with at most 'max_concurrency' requests in
+    /// flight, all paced by the client's rate limiter. A failed request only fails its own
+    /// product's entry, so one bad market doesn't lose the rest of the batch.
+    ///
+    /// # Arguments
+    ///
+    /// * 'product_ids' - market identifiers, either ProductIds or strings formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. All ids are validated before any request is sent.
+    /// * 'level' - book level, as in get_product_orderbook.
+    /// * 'max_concurrency' - most requests in flight at once. 0 is treated as 1.
+    pub async fn get_orderbooks<P: IntoProductId + Clone>(
+        &self,
+        product_ids: &[P],
+        level: OBLevel,
+        max_concurrency: usize
+    ) -> Result<HashMap<ProductId, Result<String, anyhow::Error>>, anyhow::Error> {
+        let product_ids = product_ids.iter()
+            .map(|product_id| product_id.clone().into_product_id())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let books = batch::join_limited(
+            product_ids.iter().map(|product_id| self.get_product_orderbook(product_id, level)),
+            max_concurrency,
+        ).await;
+        Ok(product_ids.into_iter().zip(books).collect())
+    }
+
+    /// Returns the best bid and best ask of a market, from its level 1 book.
+    /// Fails if either side of the book is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    pub async fn best_bid_ask(&self, product_id: impl IntoProductId) -> Result<(Quote, Quote), anyhow::Error> {
+        let product_id = product_id.into_product_id()?;
         let endpoint = format!("/products/{}/book", product_id);
-        Ok(self.get_json(&endpoint, Some(params)).await?)
+        let book: OrderBookL2 = self.get_typed(&endpoint, Some(vec![OBLevel::Level1.param_tuple()])).await?;
+        match (book.best_bid(), book.best_ask()) {
+            (Some(bid), Some(ask)) => Ok((bid.into(), ask.into())),
+            _ => bail!("order book for {} has no bid or no ask", product_id),
+        }
+    }
+
+    /// Streams an order book response into 'writer' as it arrives instead of building it up
+    /// in memory, which keeps memory flat for level 3 books. Returns the number of bytes written.
+    ///
+    /// # Arguments
+    ///
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    /// * 'level' - book level, as in get_product_orderbook.
+    /// * 'writer' - destination such as a tokio File or an async compressor. It is flushed
+    ///   but not shut down.
+    pub async fn write_product_orderbook<W: AsyncWrite + Unpin>(
+        &self,
+        product_id: impl IntoProductId,
+        level: OBLevel,
+        writer: &mut W
+    ) -> Result<u64, anyhow::Error> {
+        let params: Params = vec![level.param_tuple()];
+        let endpoint = format!("/products/{}/book", product_id.into_product_id()?);
+        self.write_json(&endpoint, Some(params), writer).await
     }
 
     /// Returns snapshot about the last trade, best bid/ask and 24h volume.
     ///
     /// # Arguments
     ///
-    /// * 'product_id' - market identifier formatted as 'BASE-QUOTE', such as 'ETH-USD'.
-    /// String can be lowercase or uppercase.
-    pub async fn get_product_ticker(&self, product_id: &str) -> Result<String, anyhow::Error> {
-        let endpoint = format!("/products/{}/ticker", product_id);
-        Ok(self.get_json(&endpoint, None).await?)
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    pub async fn get_product_ticker(&self, product_id: impl IntoProductId) -> Result<String, anyhow::Error> {
+        let endpoint = format!("/products/{}/ticker", product_id.into_product_id()?);
+        self.get_json(&endpoint, None).await
     }
 
     /// Returns a product's latest trades.
     ///
     /// # Arguments
     ///
-    /// * 'product_id' - market identifier formatted as 'BASE-QUOTE', such as 'ETH-USD'.
-    /// String can be lowercase or uppercase.
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
     /// * 'after' - optional parameter: pass in a 'Some(u64)' to parameterize a lower bound for
-    /// recent trades, and exclude trades from the response that have a lower sequence.
-    pub async fn get_product_trades(&self, product_id: &str, after: Option<u64>) -> Result<String, anyhow::Error> {
-        let endpoint = format!("/products/{}/trades", product_id);
+    ///   recent trades, and exclude trades from the response that have a lower sequence.
+    pub async fn get_product_trades(&self, product_id: impl IntoProductId, after: Option<u64>) -> Result<String, anyhow::Error> {
+        let endpoint = format!("/products/{}/trades", product_id.into_product_id()?);
 
         let maybe_params: Option<Params> = after
             .map(|after| vec![("after".to_owned(), (after + 1).to_string())]);
 
-        Ok(self.get_json(&endpoint, maybe_params).await?)
+        self.get_json(&endpoint, maybe_params).await
     }
 
     /// Return's a product's historic rates.
     ///
     /// # Arguments
-    /// * 'product_id' - market identifier formatted as 'BASE-QUOTE', such as 'ETH-USD'.
-    /// String can be lowercase or uppercase.
-    /// * 'start' - optional parameter: Start DateTime<UTC>
-    /// * 'end' - optional parameter: End DateTime<UTC>
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    /// * 'start' - optional parameter: start of the range. Accepts an Option<DateTime<Utc>>, or
+    ///   a bare DateTime in any time zone, SystemTime or i64 unix timestamp in seconds.
+    /// * 'end' - optional parameter: end of the range, accepting the same types as 'start'.
     /// * 'granularity' - optional parameter: candle size in seconds
     ///
     /// The chrono crate's ['to_rfc3339'](https://docs.rs/chrono/0.4.0/chrono/struct.DateTime.html#method.to_rfc3339)
@@ -178,57 +440,370 @@ impl CoinbasePublicClient {
     ///
     /// If start, end, and granularity parameters are left None, Coinbase will return
     /// 300 1-minute candles. Coinbase does not publish data for periods where no trades
-    /// occur. Coinbase will reject requests for more than 300 candles of any size.
+    /// occur. Coinbase will reject requests for more than 300 candles of any size, so when
+    /// start, end and granularity are all given, a range spanning more than that fails with a
+    /// candles::TooManyCandles error before anything is sent.
     pub async fn get_product_historic_rates(
         &self,
-        product_id: &str,
-        start_opt: Option<DateTime<Utc>>,
-        end_opt: Option<DateTime<Utc>>,
+        product_id: impl IntoProductId,
+        start_opt: impl Into<RangeBound>,
+        end_opt: impl Into<RangeBound>,
         granularity_opt: Option<Granularity>
     ) -> Result<String, anyhow::Error> {
-        let endpoint = format!("/products/{}/candles", product_id);
+        let endpoint = format!("/products/{}/candles", product_id.into_product_id()?);
+        let start_opt = start_opt.into().resolve().context("invalid start")?;
+        let end_opt = end_opt.into().resolve().context("invalid end")?;
+        let maybe_params = candle_params(start_opt, end_opt, granularity_opt)?;
+
+        self.get_json(&endpoint, maybe_params).await
+    }
 
-        let mut params: Params = Vec::new();
-        if start_opt.is_some() {
-            params.push(("start".to_owned(), start_opt.unwrap().to_owned().to_rfc3339()))
+    /// Returns one candle per UTC day from 'from' to 'to', inclusive, oldest first. Ranges
+    /// longer than Coinbase's 300 candle limit are fetched in several requests.
+    ///
+    /// # Arguments
+    ///
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    /// * 'from' - first day, whose candle starts at 00:00 UTC.
+    /// * 'to' - last day, included in the result.
+    ///
+    /// Days without trades have no candle, as with get_product_historic_rates.
+    pub async fn get_daily_candles(
+        &self,
+        product_id: impl IntoProductId,
+        from: NaiveDate,
+        to: NaiveDate
+    ) -> Result<Vec<Candle>, anyhow::Error> {
+        if to < from {
+            bail!("'to' date {} is before 'from' date {}", to, from);
         }
-        if end_opt.is_some() {
-            params.push(("end".to_owned(), end_opt.unwrap().to_owned().to_rfc3339()))
+        let midnight = |date: NaiveDate| date.and_time(NaiveTime::MIN).and_utc();
+        self.get_candles_chunked(product_id, midnight(from), midnight(to), Granularity::Hour24).await
+    }
+
+    /// Returns candles of a duration Coinbase doesn't offer, such as 2 hours, 4 hours or 1 week,
+    /// oldest first.
+    ///
+    /// This is derived data: the client fetches candles of the largest supported granularity
+    /// that divides 'duration' (see candles::source_granularity), in as many requests as
+    /// needed, and merges them with candles::resample_to_duration. Buckets are aligned to the
+    /// unix epoch, and the first and last buckets only cover source candles inside the range.
+    ///
+    /// # Arguments
+    ///
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    /// * 'start' - start of the range, accepting the same types as get_product_historic_rates.
+    /// * 'end' - end of the range, inclusive.
+    /// * 'duration' - candle duration. Must be a multiple of one minute.
+    pub async fn get_custom_candles(
+        &self,
+        product_id: impl IntoProductId,
+        start: impl Into<RangeBound>,
+        end: impl Into<RangeBound>,
+        duration: chrono::Duration
+    ) -> Result<Vec<Candle>, anyhow::Error> {
+        let start = start.into().resolve().context("invalid start")?.context("start is required")?;
+        let end = end.into().resolve().context("invalid end")?.context("end is required")?;
+        let granularity = candles::source_granularity(duration)?;
+
+        let source = self.get_candles_chunked(product_id, start, end, granularity).await?;
+        candles::resample_to_duration(&source, granularity, duration)
+    }
+
+    /// Fetches candles between 'start' and 'end' (inclusive) in as many requests as the 300
+    /// candle limit requires, returning them oldest first without duplicates.
+    async fn get_candles_chunked(
+        &self,
+        product_id: impl IntoProductId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        granularity: Granularity
+    ) -> Result<Vec<Candle>, anyhow::Error> {
+        if end < start {
+            bail!("start {} is after end {}", start, end);
         }
-        if granularity_opt.is_some() { params.push(granularity_opt.unwrap().param_tuple()); }
+        let endpoint = format!("/products/{}/candles", product_id.into_product_id()?);
 
-        let maybe_params: Option<Params> = match params.is_empty() {
-            true => None,
-            false => Some(params)
-        };
+        let mut candles: Vec<Candle> = Vec::new();
+        for (chunk_start, chunk_end) in candles::request_chunks(start, end, granularity) {
+            let params = candle_params(Some(chunk_start), Some(chunk_end), Some(granularity))?;
+            candles.extend(self.get_typed::<Vec<Candle>>(&endpoint, params).await?);
+        }
 
-        Ok(self.get_json(&endpoint, maybe_params).await?)
+        candles.retain(|candle| candle.time >= start && candle.time <= end);
+        candles.sort_by_key(|candle| candle.time);
+        candles.dedup_by_key(|candle| candle.time);
+        Ok(candles)
     }
 
     /// Returns a product's 24h stats.
     /// # Arguments
-    /// * 'product_id' - market identifier formatted as 'BASE-QUOTE', such as 'ETH-USD'.
-    /// String can be lowercase or uppercase.
-    pub async fn get_product_24h_stats(&self, product_id: &str) -> Result<String, anyhow::Error> {
-        let endpoint = format!("/products/{}/stats", product_id);
-        Ok(self.get_json(&endpoint, None).await?)
+    /// * 'product_id' - market identifier, either a ProductId or a string formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Strings can be lowercase or uppercase.
+    pub async fn get_product_24h_stats(&self, product_id: impl IntoProductId) -> Result<String, anyhow::Error> {
+        let endpoint = format!("/products/{}/stats", product_id.into_product_id()?);
+        self.get_json(&endpoint, None).await
     }
 
     /// Returns currencies supported by Coinbase.
     pub async fn get_currencies(&self) -> Result<String, anyhow::Error> {
         let endpoint = "/currencies";
-        Ok(self.get_json(endpoint, None).await?)
+        self.get_json(endpoint, None).await
     }
 
 
     /// Returns Coinbase's server time in both epoch and ISO format.
     pub async fn get_time(&self) -> Result<String, anyhow::Error> {
         let endpoint = "/time";
-        Ok(self.get_json(endpoint, None).await?)
+        self.get_json(endpoint, None).await
+    }
+
+    /// Samples the time endpoint several times (DEFAULT_TIME_SYNC_SAMPLES), estimates the offset
+    /// between the local and server clocks from the fastest round trip, and caches it for server_now().
+    pub async fn sync_time(&self) -> Result<ClockSkew, anyhow::Error> {
+        let url = self.build_url("/time", None)?;
+
+        let mut samples = Vec::with_capacity(DEFAULT_TIME_SYNC_SAMPLES as usize);
+        for _ in 0..DEFAULT_TIME_SYNC_SAMPLES {
+            // wait for the rate limiter before starting the clock, so throttling doesn't count as latency
+            self.wait_for_rate_limiter().await;
+            let sent_at = Utc::now();
+            let json = self.send_get(url.clone()).await?;
+            let received_at = Utc::now();
+            let server_time: ServerTime = serde_json::from_str(&json)
+                .context("failed to deserialize server time")?;
+            samples.push(TimeSample { sent_at, received_at, server_time: server_time.iso });
+        }
+
+        let skew = ClockSkew::estimate(&samples).context("no time samples collected")?;
+        *self.clock_skew.write().unwrap() = Some(skew);
+        Ok(skew)
+    }
+
+    /// Checks that Coinbase is reachable and answering sensibly, such as for a readiness probe:
+    /// fetches the server time, timing the round trip, and checks that the response parses.
+    /// Never fails, since problems are reported in the HealthReport.
+    pub async fn health_check(&self) -> HealthReport {
+        // wait for the rate limiter before starting the clock, so throttling doesn't count as latency
+        self.wait_for_rate_limiter().await;
+        let checked_at = Utc::now();
+        let started = std::time::Instant::now();
+        let result = async {
+            let json = self.send_get(self.build_url("/time", None)?).await?;
+            serde_json::from_str::<ServerTime>(&json).context("failed to deserialize server time")
+        }.await;
+
+        HealthReport {
+            checked_at,
+            latency: started.elapsed(),
+            server_time: result.as_ref().ok().map(|time| time.iso),
+            error: result.err().map(|err| format!("{:#}", err)),
+        }
+    }
+
+    /// Clock skew cached by the last call to sync_time, if any.
+    pub fn clock_skew(&self) -> Option<ClockSkew> {
+        *self.clock_skew.read().unwrap()
+    }
+
+    /// Number of unknown fields and enum values seen in responses so far, or None unless
+    /// enabled with track_unknown_fields. Fields a response repeats, such as one per product
+    /// in the product list, are counted each time.
+    pub fn unknown_field_count(&self) -> Option<u64> {
+        self.unknown_fields.as_ref().map(|count| count.load(Ordering::Relaxed))
+    }
+
+    /// Outcomes of the requests sent within the last 'window', out of the most recent 1000.
+    /// See the health module.
+    pub fn request_stats(&self, window: chrono::Duration) -> RequestStats {
+        self.request_log.stats(Utc::now() - window)
+    }
+
+    /// Estimated current server time. Equal to the local time until sync_time has been called.
+    pub fn server_now(&self) -> DateTime<Utc> {
+        let now = Utc::now();
+        self.clock_skew().map_or(now, |skew| skew.to_server_time(now))
+    }
+
+    /// Fetches ticker and 24h stats for several products concurrently and returns one
+    /// combined snapshot per product, in the order given. Requests are still paced by the
+    /// client's rate limiter.
+    ///
+    /// # Arguments
+    ///
+    /// * 'product_ids' - market identifiers, either ProductIds or strings formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. All ids are validated before any request is sent.
+    pub async fn get_market_snapshot<P: IntoProductId + Clone>(
+        &self,
+        product_ids: &[P]
+    ) -> Result<Vec<MarketSnapshot>, anyhow::Error> {
+        let product_ids = product_ids.iter()
+            .map(|product_id| product_id.clone().into_product_id())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let snapshots = product_ids.into_iter().map(|product_id| async move {
+            let ticker_endpoint = format!("/products/{}/ticker", product_id);
+            let stats_endpoint = format!("/products/{}/stats", product_id);
+            let (ticker, stats) = futures::try_join!(
+                self.get_typed::<Ticker>(&ticker_endpoint, None),
+                self.get_typed::<Stats>(&stats_endpoint, None),
+            )?;
+            Ok::<_, anyhow::Error>(MarketSnapshot { product_id, ticker, stats })
+        });
+
+        try_join_all(snapshots).await
+    }
+
+    /// Fetches the product list and each online product's 24h stats, returning the price,
+    /// 24h change and volume of every market, sorted by quote volume. Stats requests are paced
+    /// by the client's rate limiter, so with the default rate limit of 3 per second, the full
+    /// product list takes a few minutes. A product whose stats request fails is skipped rather
+    /// than failing the overview; only failing to fetch the product list is an error.
+    ///
+    /// # Arguments
+    ///
+    /// * 'quote_currency' - only include products quoted in this currency, such as 'USD'.
+    ///   None includes all products.
+    pub async fn get_market_overview(&self, quote_currency: Option<&str>) -> Result<MarketOverview, anyhow::Error> {
+        let products: Vec<Product> = self.get_typed("/products", None).await?;
+        let products: Vec<Product> = products.into_iter()
+            .filter(|product| product.status == ProductStatus::Online)
+            .filter(|product| quote_currency.map_or(true, |quote| product.quote_currency.eq_ignore_ascii_case(quote)))
+            .collect();
+        let ids: Vec<String> = products.iter().map(|product| product.id.clone()).collect();
+        let summaries = products.into_iter().map(|product| async move {
+            let product_id: ProductId = product.id.parse()?;
+            let stats: Stats = self.get_typed(&format!("/products/{}/stats", product_id), None).await?;
+            Ok::<_, anyhow::Error>(MarketSummary {
+                product_id,
+                trading_mode: product.trading_mode(),
+                display_name: product.display_name,
+                price: stats.last,
+                open_24h: stats.open,
+                volume_24h: stats.volume,
+            })
+        });
+
+        let mut overview = MarketOverview { markets: Vec::new(), skipped: Vec::new() };
+        for (id, summary) in ids.into_iter().zip(batch::join_limited(summaries, DEFAULT_OVERVIEW_CONCURRENCY).await) {
+            match summary {
+                Ok(summary) => overview.markets.push(summary),
+                Err(err) => overview.skipped.push((id, err)),
+            }
+        }
+        MarketSort::QuoteVolume.sort(&mut overview.markets);
+        Ok(overview)
+    }
+
+    /// Builds a graph of currencies connected by online markets, for use with convert_with_graph.
+    pub async fn get_product_graph(&self) -> Result<ProductGraph, anyhow::Error> {
+        let products: Vec<Product> = self.get_typed("/products", None).await?;
+        Ok(ProductGraph::new(&products))
+    }
+
+    /// Converts an amount between two currencies using last-trade prices, routing through
+    /// intermediate markets when there is no direct one (e.g. ATOM -> BTC -> USD).
+    ///
+    /// This fetches the full product list on every call. Use get_product_graph and
+    /// convert_with_graph when converting repeatedly.
+    ///
+    /// # Arguments
+    ///
+    /// * 'amount' - amount of the 'from' currency.
+    /// * 'from' - currency code to convert from, such as 'ATOM'. Case-insensitive.
+    /// * 'to' - currency code to convert to, such as 'USD'. Case-insensitive.
+    pub async fn convert(&self, amount: Decimal, from: &str, to: &str) -> Result<Conversion, anyhow::Error> {
+        let graph = self.get_product_graph().await?;
+        self.convert_with_graph(&graph, amount, from, to).await
+    }
+
+    /// Converts an amount between two currencies along the shortest path in 'graph', pricing
+    /// each hop with a live ticker.
+    pub async fn convert_with_graph(
+        &self,
+        graph: &ProductGraph,
+        amount: Decimal,
+        from: &str,
+        to: &str,
+    ) -> Result<Conversion, anyhow::Error> {
+        let path = graph.path(from, to)
+            .ok_or_else(|| anyhow!("no conversion path from {} to {}", from, to))?;
+
+        let prices = try_join_all(path.iter().map(|leg| async move {
+            let endpoint = format!("/products/{}/ticker", leg.product_id);
+            let ticker: Ticker = self.get_typed(&endpoint, None).await?;
+            Ok::<_, anyhow::Error>((leg.product_id.to_owned(), ticker.price))
+        })).await?;
+
+        conversion::price_path(amount, from, to, path, &prices.into_iter().collect())
+    }
+
+    /// Sends get message and attempts to deserialize the json response into T.
+    /// In strict mode, responses with fields the model doesn't cover fail with UnknownFields.
+    async fn get_typed<T: DeserializeOwned + Serialize>(&self, endpoint: &str, params: Option<Params>) -> Result<T, anyhow::Error> {
+        let json = self.get_json(endpoint, params).await?;
+        let parsed: T = serde_json::from_str(&json).or_else(|err| match serde_json::from_str::<ErrorMessage>(&json) {
+            Ok(error) => Err(anyhow!("coinbase rejected request to {}: {}", endpoint, error.message)),
+            Err(_) => Err(err).with_context(|| format!("failed to deserialize response from {}", endpoint)),
+        })?;
+
+        if self.deserialize_mode == DeserializeMode::Strict || self.unknown_fields.is_some() {
+            let original: serde_json::Value = serde_json::from_str(&json)?;
+            let paths = schema::unknown_fields(&original, &schema::to_value_without_extra(&parsed)?);
+            if let Some(count) = &self.unknown_fields {
+                count.fetch_add(paths.len() as u64, Ordering::Relaxed);
+            }
+            if self.deserialize_mode == DeserializeMode::Strict && !paths.is_empty() {
+                return Err(UnknownFields { endpoint: endpoint.to_owned(), paths }.into());
+            }
+        }
+        Ok(parsed)
     }
 
     /// Sends get message and attempts to return json string.
     async fn get_json(&self, endpoint: &str, params: Option<Params>) -> Result<String, anyhow::Error> {
+        let url = self.build_url(endpoint, params)?;
+        let request = || async {
+            self.wait_for_rate_limiter().await;
+            self.send_get(url.clone()).await
+        };
+        match &self.in_flight {
+            Some(in_flight) => in_flight.run(url.clone(), request).await,
+            None => request().await,
+        }
+    }
+
+    /// Sends get message and copies the response body into 'writer' chunk by chunk.
+    /// Recording clients still buffer the body, since the fixture is saved whole.
+    async fn write_json<W: AsyncWrite + Unpin>(
+        &self,
+        endpoint: &str,
+        params: Option<Params>,
+        writer: &mut W
+    ) -> Result<u64, anyhow::Error> {
+        let url = self.build_url(endpoint, params)?;
+        self.wait_for_rate_limiter().await;
+
+        let written = match &self.transport {
+            Transport::Http(http_client) => {
+                let result = http_client.get_into(&url, Duration::from_secs(self.request_timeout as u64), writer).await;
+                self.request_log.record(&result);
+                result?
+            },
+            _ => {
+                let body = self.send_get(url).await?;
+                writer.write_all(body.as_bytes()).await.context("failure while writing response")?;
+                body.len() as u64
+            },
+        };
+        writer.flush().await.context("failure while flushing writer")?;
+
+        Ok(written)
+    }
+
+    fn build_url(&self, endpoint: &str, params: Option<Params>) -> Result<Url, anyhow::Error> {
         let url_str = self.api_url.to_owned() + endpoint;
 
         let url = match params {
@@ -241,17 +816,43 @@ impl CoinbasePublicClient {
             }
         };
 
-        if self.rate_limiter.is_some() {
-            self.rate_limiter.as_ref().unwrap().until_ready().await;
+        Ok(url)
+    }
+
+    /// Waits for the rate limiter, if enabled, behind any higher priority requests (see the
+    /// scheduler module). Offline requests never wait.
+    async fn wait_for_rate_limiter(&self) {
+        if matches!(self.transport, Transport::Replay(_) | Transport::Canned(_)) {
+            return;
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            self.scheduler.acquire(rate_limiter).await;
         }
+    }
 
-        let result= self.http_client
-            .request(Method::GET, url)
-            .timeout(Duration::from_secs(self.request_timeout as u64))
-            .send().await.context("failure while sending request")?
-            .text().await.context("failure while decoding response to text")?;
+    /// Sends get message without waiting for the rate limiter, and records the outcome for
+    /// request_stats.
+    async fn send_get(&self, url: Url) -> Result<String, anyhow::Error> {
+        let result = self.send_get_unrecorded(url).await;
+        self.request_log.record(&result);
+        result
+    }
 
-        Ok(result)
+    async fn send_get_unrecorded(&self, url: Url) -> Result<String, anyhow::Error> {
+        match &self.transport {
+            Transport::Http(http_client) => self.send_http(http_client, url).await,
+            Transport::Record(http_client, fixtures) => {
+                let result = self.send_http(http_client, url.clone()).await?;
+                fixtures.save(&url, &result)?;
+                Ok(result)
+            },
+            Transport::Replay(fixtures) => fixtures.load(&url),
+            Transport::Canned(responses) => responses.load(&url),
+        }
+    }
+
+    async fn send_http(&self, http_client: &HttpClient, url: Url) -> Result<String, anyhow::Error> {
+        http_client.get(&url, Duration::from_secs(self.request_timeout as u64)).await
     }
 }
 
@@ -261,6 +862,18 @@ pub struct CoinbaseClientBuilder<'a> {
     request_timeout: Option<u8>,
     rate_limit: Option<u8>,
     burst_size: Option<u8>,
+    http2_prior_knowledge: bool,
+    http2_adaptive_window: bool,
+    pool_idle_timeout: Option<u8>,
+    tcp_keepalive: Option<u8>,
+    coalesce_requests: bool,
+    deserialize_mode: DeserializeMode,
+    track_unknown_fields: bool,
+    fixtures: Option<(FixtureMode, Fixtures)>,
+    canned_responses: Option<CannedResponses>,
+    transport: Option<Arc<dyn HttpTransport>>,
+    #[cfg(feature = "middleware")]
+    middleware_client: Option<reqwest_middleware::ClientWithMiddleware>,
 }
 
 impl CoinbaseClientBuilder<'static> {
@@ -270,6 +883,18 @@ impl CoinbaseClientBuilder<'static> {
             request_timeout: None,
             rate_limit: None,
             burst_size: None,
+            http2_prior_knowledge: false,
+            http2_adaptive_window: false,
+            pool_idle_timeout: None,
+            tcp_keepalive: None,
+            coalesce_requests: false,
+            deserialize_mode: DeserializeMode::Lenient,
+            track_unknown_fields: false,
+            fixtures: None,
+            canned_responses: None,
+            transport: None,
+            #[cfg(feature = "middleware")]
+            middleware_client: None,
         }
     }
 
@@ -301,16 +926,168 @@ impl CoinbaseClientBuilder<'static> {
         }
     }
 
+    /// Speak HTTP/2 from the first byte instead of negotiating it. Without this, connections
+    /// use HTTP/1.1 unless the TLS backend negotiates HTTP/2 through ALPN. Only use against
+    /// servers known to support HTTP/2, which Coinbase's API does. Ignored by the
+    /// hyper transport, which only speaks HTTP/1.1.
+    pub fn http2_prior_knowledge(self) -> Self {
+        Self {
+            http2_prior_knowledge: true,
+            ..self
+        }
+    }
+
+    /// Let HTTP/2 flow control windows grow with measured bandwidth, which speeds up large
+    /// responses like level 3 books. Has no effect on HTTP/1.1 connections,
+    /// so none with the hyper transport.
+    pub fn http2_adaptive_window(self, value: bool) -> Self {
+        Self {
+            http2_adaptive_window: value,
+            ..self
+        }
+    }
+
+    /// Seconds an idle pooled connection is kept for reuse (default DEFAULT_POOL_IDLE_TIMEOUT).
+    /// Requests sent within this window of the previous one skip the TCP and TLS handshakes.
+    /// Set to 0 to close connections as soon as they are idle.
+    pub fn pool_idle_timeout(self, value: u8) -> Self {
+        Self {
+            pool_idle_timeout: Some(value),
+            ..self
+        }
+    }
+
+    /// Seconds between TCP keepalive probes on open connections. Off by default. Helps pooled
+    /// connections survive NAT gateways and firewalls that drop quiet connections.
+    pub fn tcp_keepalive(self, value: u8) -> Self {
+        Self {
+            tcp_keepalive: Some(value),
+            ..self
+        }
+    }
+
+    /// Share one request between callers asking for the same endpoint and parameters at the
+    /// same time, such as dashboard widgets refreshing together. Every caller gets a copy of
+    /// the response, and only one request counts against the rate limit. Responses are not
+    /// cached once the request completes. Off by default.
+    pub fn coalesce_requests(self, value: bool) -> Self {
+        Self {
+            coalesce_requests: value,
+            ..self
+        }
+    }
+
+    /// How methods returning typed models treat fields and enum values the models don't cover
+    /// (default Lenient). Use Strict in CI to catch API changes early. Methods returning json
+    /// strings are unaffected.
+    pub fn deserialize_mode(self, value: DeserializeMode) -> Self {
+        Self {
+            deserialize_mode: value,
+            ..self
+        }
+    }
+
+    /// Count fields and enum values the typed models don't cover, across every response parsed
+    /// into a model. Read the count with unknown_field_count. Off by default, since it parses
+    /// each response a second time.
+    pub fn track_unknown_fields(self, value: bool) -> Self {
+        Self {
+            track_unknown_fields: value,
+            ..self
+        }
+    }
+
+    /// Send requests through 'client', so retry, tracing or other middleware from the
+    /// reqwest-middleware ecosystem applies to them. The connection options above are then up
+    /// to the reqwest client inside it, which should also set a user agent, since Coinbase
+    /// rejects requests without one. Rate limiting, request coalescing and fixtures still
+    /// apply on top.
+    #[cfg(feature = "middleware")]
+    pub fn middleware_client(self, client: reqwest_middleware::ClientWithMiddleware) -> Self {
+        Self {
+            middleware_client: Some(client),
+            ..self
+        }
+    }
+
+    /// Send requests through 'transport' instead of the reqwest or hyper client, such as an
+    /// adapter for another http stack. Takes precedence over middleware_client, and the
+    /// connection options above are then up to the transport. Rate limiting, request
+    /// coalescing and fixtures still apply on top.
+    pub fn transport(self, transport: impl HttpTransport + 'static) -> Self {
+        Self {
+            transport: Some(Arc::new(transport)),
+            ..self
+        }
+    }
+
+    /// Save every response into 'dir' as a fixture, for later use with replay_fixtures.
+    /// See the testing module for how fixture files are named.
+    pub fn record_fixtures(self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            fixtures: Some((FixtureMode::Record, Fixtures::new(dir))),
+            ..self
+        }
+    }
+
+    /// Serve responses from fixtures saved in 'dir' instead of calling Coinbase.
+    /// Requests with no recorded fixture fail. Rate limiting is skipped.
+    pub fn replay_fixtures(self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            fixtures: Some((FixtureMode::Replay, Fixtures::new(dir))),
+            ..self
+        }
+    }
+
+    /// Run fully offline, answering every request from 'responses' (endpoint -> json body)
+    /// instead of calling Coinbase. Takes precedence over record_fixtures and replay_fixtures.
+    /// Use replay_fixtures to serve responses from a directory instead.
+    ///
+    ///  # Example
+    ///             use std::collections::HashMap;
+    ///             use coinbase_pro_api::CoinbasePublicClient;
+    ///
+    ///             let client = CoinbasePublicClient::builder()
+    ///                 .offline(HashMap::from([
+    ///                     ("/time".to_owned(), r#"{"iso":"2022-10-10T10:00:00Z","epoch":1665396000.0}"#.to_owned()),
+    ///                 ]))
+    ///                 .build();
+    pub fn offline(self, responses: impl Into<CannedResponses>) -> Self {
+        Self {
+            canned_responses: Some(responses.into()),
+            ..self
+        }
+    }
+
     pub fn build(self) -> CoinbasePublicClient {
         let rate_limit = self.rate_limit.unwrap_or(DEFAULT_RATE_LIMIT);
         let burst_size = self.burst_size.unwrap_or(DEFAULT_BURST_SIZE);
+        let pool_idle_timeout = self.pool_idle_timeout.unwrap_or(DEFAULT_POOL_IDLE_TIMEOUT);
+        let http_options = HttpOptions {
+            pool_idle_timeout: Duration::from_secs(pool_idle_timeout as u64),
+            tcp_keepalive: self.tcp_keepalive.map(|seconds| Duration::from_secs(seconds as u64)),
+            http2_prior_knowledge: self.http2_prior_knowledge,
+            http2_adaptive_window: self.http2_adaptive_window,
+        };
+        let http_client = || {
+            if let Some(transport) = &self.transport {
+                return HttpClient::Custom(transport.clone());
+            }
+            #[cfg(feature = "middleware")]
+            if let Some(middleware_client) = &self.middleware_client {
+                return HttpClient::Middleware(middleware_client.clone());
+            }
+            HttpClient::new(&http_options)
+        };
 
         CoinbasePublicClient {
             api_url: self.api_url.unwrap_or(COINBASE_API_URL),
-            http_client: reqwest::Client::builder()
-                .user_agent(APP_USER_AGENT)
-                .build()
-                .unwrap_or_else(|_| reqwest::Client::new()),
+            transport: match (self.canned_responses, self.fixtures) {
+                (Some(responses), _) => Transport::Canned(responses),
+                (None, None) => Transport::Http(http_client()),
+                (None, Some((FixtureMode::Record, fixtures))) => Transport::Record(http_client(), fixtures),
+                (None, Some((FixtureMode::Replay, fixtures))) => Transport::Replay(fixtures),
+            },
             request_timeout: self.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT),
             rate_limiter: {
                 if rate_limit > 0 {
@@ -321,6 +1098,12 @@ impl CoinbaseClientBuilder<'static> {
                     Some(RateLimiter::direct(quota))
                 } else { None }
             },
+            scheduler: Scheduler::default(),
+            clock_skew: RwLock::new(None),
+            in_flight: self.coalesce_requests.then(InFlight::default),
+            deserialize_mode: self.deserialize_mode,
+            unknown_fields: self.track_unknown_fields.then(AtomicU64::default),
+            request_log: RequestLog::default(),
         }
     }
 }
@@ -341,34 +1124,227 @@ impl Default for CoinbaseClientBuilder<'_> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::prelude::*;
-    use std::time::SystemTime;
     use lazy_static::lazy_static;
 
-    fn print_type_of<T>(_: &T) {
-        println!("{}", std::any::type_name::<T>());
-    }
-
-    fn now_minus_x_sec(x: u64) -> DateTime<Utc> {
-        let now: DateTime<Utc> = SystemTime::now().into();
-        let x = chrono::Duration::seconds(x as i64);
-        now.checked_sub_signed(x).unwrap()
-    }
-
     lazy_static! {
         static ref client: CoinbasePublicClient = CoinbasePublicClient::builder()
             .rate_limit(1)
             .burst_size(1)
+            .replay_fixtures(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"))
             .build()
         ;
     }
 
+    #[test]
+    fn test_granularity_parsing() {
+        for granularity in Granularity::ALL {
+            assert_eq!(granularity.to_string().parse::<Granularity>().unwrap(), granularity);
+            assert_eq!(Granularity::try_from(granularity.seconds()).unwrap(), granularity);
+        }
+        assert_eq!("3600".parse::<Granularity>().unwrap(), Granularity::Hour1);
+        assert_eq!("24H".parse::<Granularity>().unwrap(), Granularity::Hour24);
+        assert!("2h".parse::<Granularity>().is_err());
+        assert!(Granularity::try_from(120).is_err());
+    }
+
+    #[test]
+    fn test_orderbook_level_parsing() {
+        for level in [OBLevel::Level1, OBLevel::Level2, OBLevel::Level3] {
+            assert_eq!(level.to_string().parse::<OBLevel>().unwrap(), level);
+        }
+        assert_eq!("Level2".parse::<OBLevel>().unwrap(), OBLevel::Level2);
+        assert!("4".parse::<OBLevel>().is_err());
+        assert!(OBLevel::try_from(0).is_err());
+    }
+
     #[tokio::test]
     async fn test_time() {
         let response = client.get_time().await;
         assert!(response.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_sync_time() {
+        let skew = client.sync_time().await;
+        assert!(skew.is_ok());
+        assert_eq!(client.clock_skew(), Some(skew.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_get_orderbooks() {
+        let books = client.get_orderbooks(&["eth-usd", "btc-usd"], OBLevel::Level2, 2).await.unwrap();
+        let eth: OrderBookL2 = serde_json::from_str(books[&"ETH-USD".parse::<ProductId>().unwrap()].as_ref().unwrap()).unwrap();
+        assert!(!eth.bids.is_empty());
+        // no btc-usd book was recorded, which only fails that product
+        assert!(books[&"BTC-USD".parse::<ProductId>().unwrap()].is_err());
+        assert!(client.get_orderbooks(&["ETH-USD", "bad"], OBLevel::Level2, 2).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_market_overview() {
+        let stats = |open: &str, last: &str, volume: &str| format!(
+            r#"{{"open": "{}", "high": "1", "low": "1", "last": "{}", "volume": "{}", "volume_30day": "1"}}"#, open, last, volume
+        );
+        let offline_client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new()
+                .with("/products", include_str!("../tests/fixtures/products.json"))
+                .with("/products/ETH-BTC/stats", stats("0.06", "0.066", "100"))
+                .with("/products/ATOM-BTC/stats", stats("0.0008", "0.0006", "20000")))
+            .build();
+
+        let overview = offline_client.get_market_overview(Some("btc")).await.unwrap();
+        assert!(overview.skipped.is_empty());
+        let mut markets = overview.markets;
+        assert_eq!(markets.iter().map(|market| market.product_id.as_str()).collect::<Vec<_>>(), ["ATOM-BTC", "ETH-BTC"]);
+        assert_eq!(markets[1].change_24h_percent(), Some(Decimal::from(10)));
+        MarketSort::Change.sort(&mut markets);
+        assert_eq!(markets[0].product_id.as_str(), "ETH-BTC");
+    }
+
+    #[tokio::test]
+    async fn test_market_overview_skips_failed_stats() {
+        let offline_client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new()
+                .with("/products", include_str!("../tests/fixtures/products.json"))
+                .with("/products/ETH-BTC/stats", include_str!("../tests/fixtures/products_eth-usd_stats.json")))
+            .build();
+
+        // only ETH-BTC has stats, so every other market is skipped
+        let overview = offline_client.get_market_overview(None).await.unwrap();
+        assert_eq!(overview.markets.iter().map(|market| market.product_id.as_str()).collect::<Vec<_>>(), ["ETH-BTC"]);
+        assert!(!overview.skipped.is_empty());
+        assert!(overview.skipped.iter().all(|(id, _)| id != "ETH-BTC"));
+    }
+
+    #[cfg(feature = "middleware")]
+    #[tokio::test]
+    async fn test_middleware_client() {
+        use reqwest_middleware::{ClientBuilder, Middleware, Next};
+        use task_local_extensions::Extensions;
+
+        /// Answers every request itself, echoing the path.
+        struct Echo;
+
+        #[async_trait::async_trait]
+        impl Middleware for Echo {
+            async fn handle(&self, req: reqwest::Request, _: &mut Extensions, _: Next<'_>) -> reqwest_middleware::Result<reqwest::Response> {
+                let body = format!(r#"{{"iso": "2022-10-10T10:00:00Z", "epoch": 1665396000, "path": "{}"}}"#, req.url().path());
+                Ok(http::Response::new(body).into())
+            }
+        }
+
+        let middleware_client = CoinbasePublicClient::builder()
+            .middleware_client(ClientBuilder::new(reqwest::Client::new()).with(Echo).build())
+            .build();
+        let time: serde_json::Value = serde_json::from_str(&middleware_client.get_time().await.unwrap()).unwrap();
+        assert_eq!(time["path"], "/time");
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let report = client.health_check().await;
+        assert!(report.is_healthy());
+        assert!(report.server_time.is_some());
+
+        let broken = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new().with("/time", "<html>bad gateway</html>"))
+            .build();
+        let report = broken.health_check().await;
+        assert!(report.error.unwrap().starts_with("failed to deserialize server time"));
+        assert_eq!(broken.request_stats(chrono::Duration::minutes(1)).requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_offline() {
+        let offline_client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new().with("/time", r#"{"iso":"2022-10-10T10:00:00Z","epoch":1665396000.0}"#))
+            .build();
+        assert!(offline_client.get_time().await.unwrap().contains("1665396000"));
+        assert!(offline_client.get_currencies().await.is_err());
+    }
+
+    #[cfg(any(feature = "reqwest", feature = "hyper"))]
+    #[tokio::test]
+    async fn test_connection_reuse() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // minimal keep-alive HTTP/1.1 server that counts accepted connections
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url: &'static str = Box::leak(format!("http://{}", listener.local_addr().unwrap()).into_boxed_str());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let body = r#"{"iso":"2022-10-10T10:00:00Z","epoch":1665396000.0}"#;
+                    let mut buf = [0u8; 4096];
+                    while let Ok(n) = socket.read(&mut buf).await {
+                        if n == 0 { break; }
+                        let response = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+                        if socket.write_all(response.as_bytes()).await.is_err() { break; }
+                    }
+                });
+            }
+        });
+
+        let local_client = CoinbasePublicClient::builder()
+            .api_url(api_url)
+            .rate_limit(0)
+            .tcp_keepalive(60)
+            .build();
+        for _ in 0..3 {
+            assert!(local_client.get_time().await.is_ok());
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_best_bid_ask() {
+        let (bid, ask) = client.best_bid_ask("eth-usd").await.unwrap();
+        assert!(bid.price < ask.price);
+        assert!(bid.size > Decimal::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_deserialize_modes() {
+        let book = r#"{"bids": [["99", "1", 1]], "asks": [["101", "2", 1]], "sequence": 7, "auction_mode": false}"#;
+        let responses = CannedResponses::new().with("/products/ETH-USD/book", book);
+
+        let lenient = CoinbasePublicClient::builder().offline(responses.clone()).build();
+        assert!(lenient.best_bid_ask("ETH-USD").await.is_ok());
+
+        let strict = CoinbasePublicClient::builder()
+            .offline(responses)
+            .deserialize_mode(DeserializeMode::Strict)
+            .build();
+        let err = strict.best_bid_ask("ETH-USD").await.unwrap_err();
+        assert_eq!(err.downcast_ref::<UnknownFields>().unwrap().paths, [".auction_mode"]);
+
+        let tracking = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new().with("/products/ETH-USD/book", book))
+            .track_unknown_fields(true)
+            .build();
+        assert_eq!(tracking.unknown_field_count(), Some(0));
+        tracking.best_bid_ask("ETH-USD").await.unwrap();
+        tracking.best_bid_ask("ETH-USD").await.unwrap();
+        assert_eq!(tracking.unknown_field_count(), Some(2));
+        assert_eq!(lenient.unknown_field_count(), None);
+    }
+
+    #[tokio::test]
+    async fn test_write_orderbook() {
+        let mut buffer: Vec<u8> = Vec::new();
+        let written = client.write_product_orderbook("ETH-USD", OBLevel::Level3, &mut buffer).await.unwrap();
+        assert_eq!(written, buffer.len() as u64);
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            client.get_product_orderbook("ETH-USD", OBLevel::Level3).await.unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_currencies() {
         let response = client.get_currencies().await;
@@ -386,7 +1362,54 @@ mod tests {
         let candles = client.get_product_historic_rates(
             "eth-usd", None, None, None
             ).await;
-        assert!(candles.is_ok())
+        assert!(candles.is_ok());
+
+        let end = chrono::DateTime::parse_from_rfc3339("2022-10-10T12:00:00+02:00").unwrap();
+        let candles = client.get_product_historic_rates(
+            "eth-usd", 1665392400, end, Some(Granularity::Minute1)
+            ).await;
+        assert!(candles.is_ok());
+
+        let candles = client.get_product_historic_rates("eth-usd", i64::MAX, None, None).await;
+        assert!(candles.is_err());
+
+        let candles = client.get_product_historic_rates(
+            "eth-usd", 1665392400, 1665392400 + 86400, Some(Granularity::Minute1)
+            ).await;
+        assert!(candles.unwrap_err().downcast_ref::<candles::TooManyCandles>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_daily_candles() {
+        // newest first, with one candle outside the requested range
+        let daily = r#"[
+            [1665446400, 1280, 1310, 1300, 1290, 100],
+            [1665360000, 1270, 1320, 1310, 1300, 120],
+            [1665273600, 1290, 1330, 1320, 1310, 90]
+        ]"#;
+        let offline_client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new().with("/products/ETH-USD/candles", daily))
+            .build();
+        let from = NaiveDate::from_ymd_opt(2022, 10, 9).unwrap();
+        let to = NaiveDate::from_ymd_opt(2022, 10, 10).unwrap();
+
+        let candles = offline_client.get_daily_candles("ETH-USD", from, to).await.unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].time.date_naive(), from);
+        assert_eq!(candles[1].time.date_naive(), to);
+
+        // a year spans two requests, each answered with the same canned candles
+        let candles = offline_client.get_daily_candles("ETH-USD", from - chrono::Duration::days(365), to).await.unwrap();
+        assert_eq!(candles.len(), 2);
+        assert!(offline_client.get_daily_candles("ETH-USD", to, from).await.is_err());
+
+        // daily candles merged into two-day candles aligned to the epoch
+        let start = from.and_time(NaiveTime::MIN).and_utc();
+        let end = start + chrono::Duration::days(2);
+        let candles = offline_client.get_custom_candles("ETH-USD", start, end, chrono::Duration::days(2)).await.unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0].volume, Decimal::from(210));
+        assert_eq!(candles[1].volume, Decimal::from(100));
     }
 
     #[tokio::test]
@@ -416,6 +1439,26 @@ mod tests {
         assert!(orderbook_lvl3.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_market_snapshot() {
+        let snapshots = client.get_market_snapshot(&["ETH-USD", "BTC-USD"]).await;
+        assert!(snapshots.is_ok());
+        let snapshots = snapshots.unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].product_id.as_str(), "BTC-USD");
+
+        // malformed ids are rejected before anything is requested
+        let snapshots = client.get_market_snapshot(&["ETH-USD", "BTCUSD"]).await;
+        assert!(snapshots.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_convert() {
+        let conversion = client.convert(Decimal::ONE, "ATOM", "USD").await;
+        assert!(conversion.is_ok());
+        assert!(!conversion.unwrap().hops.is_empty());
+    }
+
     #[tokio::test]
     async fn get_products() {
         let products = client.get_products().await;
@@ -0,0 +1,978 @@
+//! Typed models for Coinbase Pro responses.
+//!
+//! Market data models are generic over their number type, which defaults to Decimal. Use
+//! RawNumber instead to keep prices and sizes exactly as Coinbase sent them, for audit trails
+//! that must not alter numeric representations:
+//!
+//!             use coinbase_pro_api::models::{RawNumber, Ticker};
+//!
+//!             let json = r#"{"trade_id": 1, "price": "1300.510", "size": "1e-2", "bid": "1300.5",
+//!                 "ask": "1300.51", "volume": "12", "time": "2022-10-10T10:00:00Z"}"#;
+//!             let ticker: Ticker<RawNumber> = serde_json::from_str(json).unwrap();
+//!             assert_eq!(ticker.size.as_str(), "1e-2");
+//!             assert_eq!(serde_json::to_string(&ticker.price).unwrap(), r#""1300.510""#);
+
+// std
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+// external
+use chrono::{DateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use serde_json::Value;
+use serde_json::value::RawValue;
+// crate
+use crate::product_id::ProductId;
+
+/// Enum representing the side of a trade or order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+/// Enum representing Coinbase's order types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderType {
+    Limit,
+    Market,
+    Stop,
+}
+
+/// Enum representing how long a limit order rests on the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimeInForce {
+    /// Good till canceled.
+    #[serde(rename = "GTC")]
+    GoodTillCanceled,
+    /// Good till time, canceled after a given time.
+    #[serde(rename = "GTT")]
+    GoodTillTime,
+    /// Immediate or cancel: fill what is possible and cancel the rest.
+    #[serde(rename = "IOC")]
+    ImmediateOrCancel,
+    /// Fill or kill: fill entirely or cancel.
+    #[serde(rename = "FOK")]
+    FillOrKill,
+}
+
+/// Enum representing what happens when an order would trade against one from the same user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    /// Decrease the larger order by the smaller one and cancel the smaller one.
+    #[serde(rename = "dc")]
+    DecreaseAndCancel,
+    /// Cancel the resting order.
+    #[serde(rename = "co")]
+    CancelOldest,
+    /// Cancel the incoming order.
+    #[serde(rename = "cn")]
+    CancelNewest,
+    /// Cancel both orders.
+    #[serde(rename = "cb")]
+    CancelBoth,
+}
+
+/// A number exactly as it appeared in a response, whether sent as a json string like
+/// "1300.50" or a bare json number like 1300.50. Serializes back to the same text and quoting.
+///
+/// Only supported with serde_json, which all responses are parsed with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawNumber {
+    text: String,
+    quoted: bool,
+}
+
+impl RawNumber {
+    /// The number's text, without quotes.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether Coinbase sent the number as a json string.
+    pub fn is_quoted(&self) -> bool {
+        self.quoted
+    }
+
+    /// Parses the number, accepting scientific notation.
+    pub fn to_decimal(&self) -> Result<Decimal, anyhow::Error> {
+        Decimal::from_str_exact(&self.text)
+            .or_else(|_| Decimal::from_scientific(&self.text))
+            .map_err(|err| anyhow::anyhow!("invalid number '{}': {}", self.text, err))
+    }
+}
+
+impl fmt::Display for RawNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawNumber {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = <Box<RawValue>>::deserialize(deserializer)?;
+        let json = raw.get();
+        if json.starts_with('"') {
+            let text: String = serde_json::from_str(json).map_err(D::Error::custom)?;
+            Ok(RawNumber { text, quoted: true })
+        } else if json.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+            Ok(RawNumber { text: json.to_owned(), quoted: false })
+        } else {
+            Err(D::Error::custom(format!("expected a number or numeric string, got {}", json)))
+        }
+    }
+}
+
+impl Serialize for RawNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.quoted {
+            serializer.serialize_str(&self.text)
+        } else {
+            RawValue::from_string(self.text.clone())
+                .map_err(serde::ser::Error::custom)?
+                .serialize(serializer)
+        }
+    }
+}
+
+/// A single trade print, as returned by the trades endpoint.
+///
+/// Note that Coinbase reports the maker's side, so a 'sell' print was lifted by a buyer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trade<N = Decimal> {
+    pub trade_id: u64,
+    pub time: DateTime<Utc>,
+    pub price: N,
+    pub size: N,
+    pub side: Side,
+    /// Fields Coinbase sent that this model doesn't cover. See the schema module.
+    #[serde(flatten, skip_serializing_if = "crate::schema::skip_extra")]
+    pub extra: HashMap<String, Value>,
+}
+
+/// A single OHLCV candle. Coinbase serializes candles as
+/// [timestamp, low, high, open, close, volume], where timestamp marks the start of the bucket.
+/// Candles serialize back into the same format, dropping any fraction of a second from 'time'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candle<N = Decimal> {
+    pub time: DateTime<Utc>,
+    pub low: N,
+    pub high: N,
+    pub open: N,
+    pub close: N,
+    pub volume: N,
+}
+
+impl<'de, N: Deserialize<'de>> Deserialize<'de> for Candle<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (timestamp, low, high, open, close, volume): (i64, N, N, N, N, N) =
+            Deserialize::deserialize(deserializer)?;
+        let time = Utc.timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or_else(|| D::Error::custom("candle timestamp out of range"))?;
+        Ok(Candle { time, low, high, open, close, volume })
+    }
+}
+
+impl<N: Serialize> Serialize for Candle<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.time.timestamp(), &self.low, &self.high, &self.open, &self.close, &self.volume).serialize(serializer)
+    }
+}
+
+/// Server time, as returned by the time endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerTime {
+    pub iso: DateTime<Utc>,
+    pub epoch: f64,
+    /// Fields Coinbase sent that this model doesn't cover. See the schema module.
+    #[serde(flatten, skip_serializing_if = "crate::schema::skip_extra")]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Snapshot of the last trade, best bid/ask and 24h volume, as returned by the ticker endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ticker<N = Decimal> {
+    pub trade_id: u64,
+    pub price: N,
+    pub size: N,
+    pub bid: N,
+    pub ask: N,
+    pub volume: N,
+    pub time: DateTime<Utc>,
+    /// Fields Coinbase sent that this model doesn't cover. See the schema module.
+    #[serde(flatten, skip_serializing_if = "crate::schema::skip_extra")]
+    pub extra: HashMap<String, Value>,
+}
+
+impl Ticker {
+    /// Best ask minus best bid.
+    pub fn spread(&self) -> Decimal {
+        self.ask - self.bid
+    }
+
+    /// Halfway between best bid and best ask.
+    pub fn mid(&self) -> Decimal {
+        (self.bid + self.ask) / Decimal::TWO
+    }
+
+    /// Spread in basis points of the mid price. None if the mid price is zero.
+    pub fn spread_bps(&self) -> Option<Decimal> {
+        spread_bps(self.spread(), self.mid())
+    }
+}
+
+/// 24h stats for a product, as returned by the stats endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats<N = Decimal> {
+    pub open: N,
+    pub high: N,
+    pub low: N,
+    pub last: N,
+    pub volume: N,
+    pub volume_30day: N,
+    /// Fields Coinbase sent that this model doesn't cover. See the schema module.
+    #[serde(flatten, skip_serializing_if = "crate::schema::skip_extra")]
+    pub extra: HashMap<String, Value>,
+}
+
+/// Ticker and 24h stats for a single product.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketSnapshot {
+    pub product_id: ProductId,
+    pub ticker: Ticker,
+    pub stats: Stats,
+}
+
+/// Price, 24h change and volume of a single product, as on a markets screen. Returned by
+/// get_market_overview.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketSummary {
+    pub product_id: ProductId,
+    pub display_name: String,
+    pub trading_mode: TradingMode,
+    /// Last trade price.
+    pub price: Decimal,
+    /// Price 24 hours ago.
+    pub open_24h: Decimal,
+    /// Volume over the last 24 hours, in the base currency.
+    pub volume_24h: Decimal,
+}
+
+impl MarketSummary {
+    /// Price change over the last 24 hours.
+    pub fn change_24h(&self) -> Decimal {
+        self.price - self.open_24h
+    }
+
+    /// Price change over the last 24 hours as a percentage of the open. None if the open is
+    /// zero.
+    pub fn change_24h_percent(&self) -> Option<Decimal> {
+        (self.change_24h() * Decimal::ONE_HUNDRED).checked_div(self.open_24h)
+    }
+
+    /// Volume over the last 24 hours in the quote currency, valued at the last price. Only
+    /// comparable between products with the same quote currency.
+    pub fn quote_volume_24h(&self) -> Decimal {
+        self.volume_24h * self.price
+    }
+}
+
+/// Result of get_market_overview.
+#[derive(Debug)]
+pub struct MarketOverview {
+    /// Summaries of the products whose stats were fetched, sorted by quote volume.
+    pub markets: Vec<MarketSummary>,
+    /// Products left out because their id didn't parse or their stats request failed, with
+    /// the error, in product list order.
+    pub skipped: Vec<(String, anyhow::Error)>,
+}
+
+/// Orderings for a list of MarketSummary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarketSort {
+    /// Alphabetically by product id.
+    ProductId,
+    /// Highest price first.
+    Price,
+    /// Biggest 24h percentage gain first, with products without one last.
+    Change,
+    /// Highest 24h quote volume first.
+    QuoteVolume,
+}
+
+impl MarketSort {
+    pub fn sort(self, markets: &mut [MarketSummary]) {
+        match self {
+            MarketSort::ProductId => markets.sort_by(|a, b| a.product_id.cmp(&b.product_id)),
+            MarketSort::Price => markets.sort_by_key(|market| Reverse(market.price)),
+            MarketSort::Change => markets.sort_by_key(|market| Reverse(market.change_24h_percent())),
+            MarketSort::QuoteVolume => markets.sort_by_key(|market| Reverse(market.quote_volume_24h())),
+        }
+    }
+}
+
+/// Listing status of a product.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProductStatus {
+    Online,
+    Offline,
+    Delisted,
+    Internal,
+    /// Any status Coinbase adds that this crate doesn't know about yet.
+    #[serde(other)]
+    Unknown,
+}
+
+/// What kind of orders a product currently accepts, derived from its status and flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TradingMode {
+    /// Accepts all orders.
+    Normal,
+    /// Only orders that add liquidity.
+    PostOnly,
+    /// Only limit orders.
+    LimitOnly,
+    /// Only cancellations.
+    CancelOnly,
+    /// Not online, or trading disabled.
+    Halted,
+}
+
+impl TradingMode {
+    /// Whether any kind of order is not accepted. Market makers should stop quoting in
+    /// CancelOnly and Halted, and takers in any restricted mode.
+    pub fn is_restricted(&self) -> bool {
+        *self != TradingMode::Normal
+    }
+}
+
+/// Information about a single market, as returned by the products endpoints.
+///
+/// Coinbase has dropped some size limits from this payload over time, so they are optional.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Product {
+    pub id: String,
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub quote_increment: Decimal,
+    pub base_increment: Decimal,
+    pub display_name: String,
+    #[serde(default)]
+    pub base_min_size: Option<Decimal>,
+    #[serde(default)]
+    pub base_max_size: Option<Decimal>,
+    #[serde(default)]
+    pub min_market_funds: Option<Decimal>,
+    #[serde(default)]
+    pub max_market_funds: Option<Decimal>,
+    pub status: ProductStatus,
+    #[serde(default)]
+    pub status_message: String,
+    #[serde(default)]
+    pub post_only: bool,
+    #[serde(default)]
+    pub limit_only: bool,
+    #[serde(default)]
+    pub cancel_only: bool,
+    #[serde(default)]
+    pub trading_disabled: bool,
+    /// Fields Coinbase sent that this model doesn't cover. See the schema module.
+    #[serde(flatten, skip_serializing_if = "crate::schema::skip_extra")]
+    pub extra: HashMap<String, Value>,
+}
+
+impl Product {
+    /// Current trading mode. The most restrictive flag wins when several are set.
+    pub fn trading_mode(&self) -> TradingMode {
+        if self.status != ProductStatus::Online || self.trading_disabled {
+            TradingMode::Halted
+        } else if self.cancel_only {
+            TradingMode::CancelOnly
+        } else if self.limit_only {
+            TradingMode::LimitOnly
+        } else if self.post_only {
+            TradingMode::PostOnly
+        } else {
+            TradingMode::Normal
+        }
+    }
+
+    /// Formats a price for an order payload: rounded towards zero to a multiple of
+    /// quote_increment, with exactly its precision and never in scientific notation.
+    pub fn format_price(&self, price: Decimal) -> String {
+        format_to_increment(price, self.quote_increment)
+    }
+
+    /// Formats a size in the base currency like format_price, using base_increment.
+    pub fn format_size(&self, size: Decimal) -> String {
+        format_to_increment(size, self.base_increment)
+    }
+
+    /// Formats funds in the quote currency like format_price.
+    pub fn format_funds(&self, funds: Decimal) -> String {
+        format_to_increment(funds, self.quote_increment)
+    }
+
+    /// Checks an order against this product's size, funds and increment limits and its
+    /// trading mode, returning every violation found. An empty list means the order is valid
+    /// as far as the product metadata can tell.
+    ///
+    /// # Arguments
+    ///
+    /// * 'side' - side of the order. Decides what 'size_or_funds' means for market orders.
+    ///
+    /// * 'price' - limit price, or None for a market order.
+    ///
+    /// * 'size_or_funds' - size in the base currency for limit orders and market sells, and
+    ///   funds in the quote currency for market buys.
+    pub fn validate_order(&self, side: Side, price: Option<Decimal>, size_or_funds: Decimal) -> Vec<OrderViolation> {
+        let mut violations = Vec::new();
+        match self.trading_mode() {
+            mode @ (TradingMode::CancelOnly | TradingMode::Halted) => violations.push(OrderViolation::TradingRestricted(mode)),
+            mode @ (TradingMode::LimitOnly | TradingMode::PostOnly) if price.is_none() => {
+                violations.push(OrderViolation::TradingRestricted(mode));
+            },
+            _ => {},
+        }
+
+        if let Some(price) = price {
+            if price <= Decimal::ZERO || !is_multiple(price, self.quote_increment) {
+                violations.push(OrderViolation::InvalidPrice { price, increment: self.quote_increment });
+            }
+        }
+
+        if price.is_none() && side == Side::Buy {
+            let funds = size_or_funds;
+            if funds <= Decimal::ZERO || !is_multiple(funds, self.quote_increment) {
+                violations.push(OrderViolation::InvalidFunds { funds, increment: self.quote_increment });
+            }
+            if let Some(min) = self.min_market_funds.filter(|min| funds < *min) {
+                violations.push(OrderViolation::BelowMinFunds { funds, min });
+            }
+            if let Some(max) = self.max_market_funds.filter(|max| funds > *max) {
+                violations.push(OrderViolation::ExceedsMaxFunds { funds, max });
+            }
+        } else {
+            let size = size_or_funds;
+            if size <= Decimal::ZERO || !is_multiple(size, self.base_increment) {
+                violations.push(OrderViolation::InvalidSize { size, increment: self.base_increment });
+            }
+            if let Some(min) = self.base_min_size.filter(|min| size < *min) {
+                violations.push(OrderViolation::BelowMinSize { size, min });
+            }
+            if let Some(max) = self.base_max_size.filter(|max| size > *max) {
+                violations.push(OrderViolation::ExceedsMaxSize { size, max });
+            }
+        }
+        violations
+    }
+}
+
+/// Whether 'value' is a whole number of 'increment's. A zero increment allows any value.
+fn is_multiple(value: Decimal, increment: Decimal) -> bool {
+    increment.is_zero() || (value % increment).is_zero()
+}
+
+/// Rounds 'value' towards zero to a multiple of 'increment' and formats it with exactly as
+/// many decimal places as the increment has, ignoring its trailing zeros.
+fn format_to_increment(value: Decimal, increment: Decimal) -> String {
+    let increment = increment.normalize();
+    if increment.is_zero() {
+        return value.normalize().to_string();
+    }
+    let mut rounded = (value / increment).trunc() * increment;
+    rounded.rescale(increment.scale());
+    rounded.to_string()
+}
+
+/// Reason an order breaks a product's limits, as returned by Product::validate_order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderViolation {
+    /// The product's trading mode doesn't accept this kind of order.
+    TradingRestricted(TradingMode),
+    /// Price is not positive or not a multiple of quote_increment.
+    InvalidPrice { price: Decimal, increment: Decimal },
+    /// Size is not positive or not a multiple of base_increment.
+    InvalidSize { size: Decimal, increment: Decimal },
+    /// Funds are not positive or not a multiple of quote_increment.
+    InvalidFunds { funds: Decimal, increment: Decimal },
+    BelowMinSize { size: Decimal, min: Decimal },
+    ExceedsMaxSize { size: Decimal, max: Decimal },
+    BelowMinFunds { funds: Decimal, min: Decimal },
+    ExceedsMaxFunds { funds: Decimal, max: Decimal },
+}
+
+impl fmt::Display for OrderViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderViolation::TradingRestricted(mode) => write!(f, "product is in {:?} mode", mode),
+            OrderViolation::InvalidPrice { price, increment } => write!(f, "price {} is not a positive multiple of {}", price, increment),
+            OrderViolation::InvalidSize { size, increment } => write!(f, "size {} is not a positive multiple of {}", size, increment),
+            OrderViolation::InvalidFunds { funds, increment } => write!(f, "funds {} are not a positive multiple of {}", funds, increment),
+            OrderViolation::BelowMinSize { size, min } => write!(f, "size {} is below the minimum of {}", size, min),
+            OrderViolation::ExceedsMaxSize { size, max } => write!(f, "size {} exceeds the maximum of {}", size, max),
+            OrderViolation::BelowMinFunds { funds, min } => write!(f, "funds {} are below the minimum of {}", funds, min),
+            OrderViolation::ExceedsMaxFunds { funds, max } => write!(f, "funds {} exceed the maximum of {}", funds, max),
+        }
+    }
+}
+
+/// A single aggregated price level. Coinbase serializes levels as [price, size, num_orders].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookLevel<N = Decimal> {
+    pub price: N,
+    pub size: N,
+    pub num_orders: u64,
+}
+
+impl<'de, N: Deserialize<'de>> Deserialize<'de> for BookLevel<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (price, size, num_orders) = Deserialize::deserialize(deserializer)?;
+        Ok(BookLevel { price, size, num_orders })
+    }
+}
+
+impl<N: Serialize> Serialize for BookLevel<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.price, &self.size, self.num_orders).serialize(serializer)
+    }
+}
+
+/// Price and size at one side of the top of the book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quote {
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+impl From<&BookLevel> for Quote {
+    fn from(level: &BookLevel) -> Self {
+        Quote { price: level.price, size: level.size }
+    }
+}
+
+/// Aggregated order book, as returned by the book endpoint at level 1 or 2.
+/// Bids are sorted best (highest) first and asks best (lowest) first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderBookL2<N = Decimal> {
+    pub sequence: u64,
+    pub bids: Vec<BookLevel<N>>,
+    pub asks: Vec<BookLevel<N>>,
+    /// Fields Coinbase sent that this model doesn't cover. See the schema module.
+    #[serde(flatten, skip_serializing_if = "crate::schema::skip_extra")]
+    pub extra: HashMap<String, Value>,
+}
+
+impl OrderBookL2 {
+    pub fn best_bid(&self) -> Option<&BookLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&BookLevel> {
+        self.asks.first()
+    }
+
+    /// Best ask minus best bid. None if either side is empty.
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    /// Halfway between best bid and best ask. None if either side is empty.
+    pub fn mid(&self) -> Option<Decimal> {
+        Some((self.best_bid()?.price + self.best_ask()?.price) / Decimal::TWO)
+    }
+
+    /// Spread in basis points of the mid price. None if either side is empty.
+    pub fn spread_bps(&self) -> Option<Decimal> {
+        spread_bps(self.spread()?, self.mid()?)
+    }
+
+    /// Order book imbalance over the best 'levels' levels of each side:
+    /// (bid size - ask size) / (bid size + ask size).
+    ///
+    /// Ranges from -1 (only asks) to 1 (only bids), with 0 meaning balanced.
+    /// None if both sides are empty.
+    pub fn imbalance(&self, levels: usize) -> Option<Decimal> {
+        imbalance(
+            self.bids.iter().take(levels).map(|level| level.size).sum(),
+            self.asks.iter().take(levels).map(|level| level.size).sum(),
+        )
+    }
+
+    /// Order book imbalance, as in imbalance, counting only levels priced within 'bps' basis
+    /// points of the mid price. None if either side is empty or nothing is within the band.
+    pub fn imbalance_within_bps(&self, bps: Decimal) -> Option<Decimal> {
+        let (bids, asks) = self.levels_within_bps(bps)?;
+        imbalance(
+            bids.iter().map(|level| level.size).sum(),
+            asks.iter().map(|level| level.size).sum(),
+        )
+    }
+
+    /// Bids and asks priced within 'bps' basis points of the mid price, best first.
+    /// None if either side is empty.
+    pub fn levels_within_bps(&self, bps: Decimal) -> Option<(&[BookLevel], &[BookLevel])> {
+        let mid = self.mid()?;
+        let band = mid * bps / Decimal::from(10_000);
+        let bids = self.bids.iter().take_while(|level| level.price >= mid - band).count();
+        let asks = self.asks.iter().take_while(|level| level.price <= mid + band).count();
+        Some((&self.bids[..bids], &self.asks[..asks]))
+    }
+
+    /// Total size resting between the top of the book and 'price', inclusive: asks priced at
+    /// or below 'price' when it is at or above the best ask, bids priced at or above it when it
+    /// is at or below the best bid, and zero inside the spread.
+    pub fn cumulative_size_to(&self, price: Decimal) -> Decimal {
+        match (self.best_bid(), self.best_ask()) {
+            (_, Some(ask)) if price >= ask.price => self.asks.iter()
+                .take_while(|level| level.price <= price)
+                .map(|level| level.size)
+                .sum(),
+            (Some(bid), _) if price <= bid.price => self.bids.iter()
+                .take_while(|level| level.price >= price)
+                .map(|level| level.size)
+                .sum(),
+            _ => Decimal::ZERO,
+        }
+    }
+
+    /// Average fill price of a market order of 'size' walking the book: buys take asks and
+    /// sells take bids. Comparing it with the mid price estimates the order's market impact.
+    /// None if 'size' isn't positive or the visible book is too thin to fill it.
+    pub fn price_for_size(&self, side: Side, size: Decimal) -> Option<Decimal> {
+        if size <= Decimal::ZERO {
+            return None;
+        }
+        let levels = match side {
+            Side::Buy => &self.asks,
+            Side::Sell => &self.bids,
+        };
+        let mut remaining = size;
+        let mut cost = Decimal::ZERO;
+        for level in levels {
+            let filled = remaining.min(level.size);
+            cost += filled * level.price;
+            remaining -= filled;
+            if remaining.is_zero() {
+                return Some(cost / size);
+            }
+        }
+        None
+    }
+
+    /// Average of the prices a buy and a sell of 'size' would fill at, a reference price that
+    /// small orders at the top of the book can't move the way they move the mid price. None if
+    /// either side is too thin to fill 'size'.
+    pub fn depth_weighted_mid(&self, size: Decimal) -> Option<Decimal> {
+        Some((self.price_for_size(Side::Buy, size)? + self.price_for_size(Side::Sell, size)?) / Decimal::TWO)
+    }
+}
+
+fn imbalance(bid_size: Decimal, ask_size: Decimal) -> Option<Decimal> {
+    (bid_size - ask_size).checked_div(bid_size + ask_size)
+}
+
+fn spread_bps(spread: Decimal, mid: Decimal) -> Option<Decimal> {
+    (spread * Decimal::from(10_000)).checked_div(mid)
+}
+
+/// Identifier of an order. With the 'uuid' feature this is a uuid::Uuid, so malformed ids
+/// are rejected while parsing. Otherwise it is the id as Coinbase sent it.
+#[cfg(feature = "uuid")]
+pub type OrderId = uuid::Uuid;
+/// Identifier of an order. With the 'uuid' feature this is a uuid::Uuid, so malformed ids
+/// are rejected while parsing. Otherwise it is the id as Coinbase sent it.
+#[cfg(not(feature = "uuid"))]
+pub type OrderId = String;
+
+/// A single resting order. Coinbase serializes level 3 entries as [price, size, order_id].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookOrder<N = Decimal> {
+    pub price: N,
+    pub size: N,
+    pub order_id: OrderId,
+}
+
+impl<'de, N: Deserialize<'de>> Deserialize<'de> for BookOrder<N> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (price, size, order_id) = Deserialize::deserialize(deserializer)?;
+        Ok(BookOrder { price, size, order_id })
+    }
+}
+
+impl<N: Serialize> Serialize for BookOrder<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.price, &self.size, &self.order_id).serialize(serializer)
+    }
+}
+
+/// Full, unaggregated order book, as returned by the book endpoint at level 3.
+/// Bids are sorted best (highest) first and asks best (lowest) first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderBookL3<N = Decimal> {
+    pub sequence: u64,
+    pub bids: Vec<BookOrder<N>>,
+    pub asks: Vec<BookOrder<N>>,
+    /// Fields Coinbase sent that this model doesn't cover. See the schema module.
+    #[serde(flatten, skip_serializing_if = "crate::schema::skip_extra")]
+    pub extra: HashMap<String, Value>,
+}
+
+impl OrderBookL3 {
+    /// Aggregates orders into price levels, keeping the best 'depth' levels of each side,
+    /// or every level if 'depth' is None.
+    pub fn to_l2(&self, depth: Option<usize>) -> OrderBookL2 {
+        let depth = depth.unwrap_or(usize::MAX);
+        OrderBookL2 {
+            sequence: self.sequence,
+            bids: aggregate_orders(&self.bids, true).into_iter().take(depth).collect(),
+            asks: aggregate_orders(&self.asks, false).into_iter().take(depth).collect(),
+            extra: self.extra.clone(),
+        }
+    }
+}
+
+impl From<&OrderBookL3> for OrderBookL2 {
+    fn from(book: &OrderBookL3) -> Self {
+        book.to_l2(None)
+    }
+}
+
+fn aggregate_orders(orders: &[BookOrder], descending: bool) -> Vec<BookLevel> {
+    let mut levels: BTreeMap<Decimal, BookLevel> = BTreeMap::new();
+    for order in orders {
+        let level = levels.entry(order.price).or_insert(BookLevel {
+            price: order.price,
+            size: Decimal::ZERO,
+            num_orders: 0,
+        });
+        level.size += order.size;
+        level.num_orders += 1;
+    }
+    match descending {
+        true => levels.into_values().rev().collect(),
+        false => levels.into_values().collect(),
+    }
+}
+
+/// Error body returned by Coinbase for rejected requests.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ErrorMessage {
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+    use serde::de::DeserializeOwned;
+
+    /// Parses 'json', then checks that serializing and parsing again gives the same value.
+    fn assert_round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(json: &str) -> T {
+        let parsed: T = serde_json::from_str(json).unwrap();
+        let reparsed: T = serde_json::from_str(&serde_json::to_string(&parsed).unwrap()).unwrap();
+        assert_eq!(parsed, reparsed);
+        parsed
+    }
+
+    #[test]
+    fn test_raw_numbers() {
+        let candles: Vec<Candle<RawNumber>> = serde_json::from_str("[[1665396000, 96.10, 99, 97, 98, 3.50000]]").unwrap();
+        assert_eq!(candles[0].volume.as_str(), "3.50000");
+        assert!(!candles[0].low.is_quoted());
+        assert_eq!(serde_json::to_string(&candles).unwrap(), "[[1665396000,96.10,99,97,98,3.50000]]");
+        assert_eq!(candles[0].low.to_decimal().unwrap(), Decimal::new(9610, 2));
+
+        let book: OrderBookL3<RawNumber> = serde_json::from_str(&std::fs::read_to_string(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/products_eth-usd_book__level=3.json")
+        ).unwrap()).unwrap();
+        assert_eq!(book.bids[0].size.as_str(), "4.78907871");
+        assert!(serde_json::from_str::<RawNumber>("true").is_err());
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let fixture = |name: &str| std::fs::read_to_string(format!("{}/tests/fixtures/{}.json", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+
+        assert_round_trip::<Vec<Product>>(&fixture("products"));
+        assert_round_trip::<Ticker>(&fixture("products_eth-usd_ticker"));
+        assert_round_trip::<Stats>(&fixture("products_eth-usd_stats"));
+        assert_round_trip::<ServerTime>(&fixture("time"));
+        assert_round_trip::<Vec<Trade>>(&fixture("products_eth-usd_trades"));
+        assert_round_trip::<Vec<Candle>>(&fixture("products_eth-usd_candles"));
+        assert_round_trip::<OrderBookL2>(&fixture("products_eth-usd_book__level=2"));
+        let l3 = assert_round_trip::<OrderBookL3>(&fixture("products_eth-usd_book__level=3"));
+
+        // numbers keep their exact scale, e.g. trailing zeros
+        let product: Product = assert_round_trip(&fixture("products_eth-usd"));
+        assert_eq!(serde_json::to_value(product.base_increment).unwrap(), serde_json::json!("0.00000001"));
+
+        let snapshot = MarketSnapshot {
+            product_id: "ETH-USD".parse().unwrap(),
+            ticker: serde_json::from_str(&fixture("products_eth-usd_ticker")).unwrap(),
+            stats: serde_json::from_str(&fixture("products_eth-usd_stats")).unwrap(),
+        };
+        assert_round_trip::<MarketSnapshot>(&serde_json::to_string(&snapshot).unwrap());
+        assert_round_trip::<Quote>(&serde_json::to_string(&Quote::from(&l3.to_l2(None).bids[0])).unwrap());
+        assert_round_trip::<TradingMode>(r#""CancelOnly""#);
+    }
+
+    #[test]
+    fn test_order_enum_strings() {
+        assert_eq!(serde_json::to_string(&Side::Sell).unwrap(), r#""sell""#);
+        assert_eq!(serde_json::to_string(&OrderType::Limit).unwrap(), r#""limit""#);
+        assert_eq!(serde_json::to_string(&TimeInForce::ImmediateOrCancel).unwrap(), r#""IOC""#);
+        assert_eq!(serde_json::to_string(&SelfTradePrevention::CancelBoth).unwrap(), r#""cb""#);
+        assert_eq!(serde_json::from_str::<TimeInForce>(r#""GTT""#).unwrap(), TimeInForce::GoodTillTime);
+        assert_eq!(serde_json::from_str::<SelfTradePrevention>(r#""dc""#).unwrap(), SelfTradePrevention::DecreaseAndCancel);
+        assert!(serde_json::from_str::<OrderType>(r#""stop_limit""#).is_err());
+    }
+
+    #[test]
+    fn test_parse_ticker() {
+        let ticker: Ticker = serde_json::from_str(r#"{
+            "ask": "1300.51", "bid": "1300.50", "volume": "123456.789", "trade_id": 86326522,
+            "price": "1300.51", "size": "0.01", "time": "2022-10-10T10:00:00.123456Z"
+        }"#).unwrap();
+        assert_eq!(ticker.trade_id, 86326522);
+        assert_eq!(ticker.bid, Decimal::new(130050, 2));
+        assert_eq!(ticker.spread(), Decimal::new(1, 2));
+        assert_eq!(ticker.mid(), Decimal::new(1300505, 3));
+        assert_eq!(ticker.spread_bps().unwrap().round_dp(4), Decimal::new(769, 4));
+    }
+
+    #[test]
+    fn test_parse_product() {
+        let product: Product = serde_json::from_str(r#"{
+            "id": "ETH-USD", "base_currency": "ETH", "quote_currency": "USD",
+            "quote_increment": "0.01", "base_increment": "0.00000001", "display_name": "ETH/USD",
+            "min_market_funds": "1", "margin_enabled": false, "fx_stablecoin": false,
+            "max_slippage_percentage": "0.02000000", "post_only": false, "limit_only": false,
+            "cancel_only": false, "trading_disabled": false, "status": "online", "status_message": "",
+            "auction_mode": false
+        }"#).unwrap();
+        assert_eq!(product.quote_increment, Decimal::new(1, 2));
+        assert_eq!(product.min_market_funds, Some(Decimal::ONE));
+        assert!(product.base_min_size.is_none());
+        assert_eq!(product.status, ProductStatus::Online);
+        assert_eq!(product.trading_mode(), TradingMode::Normal);
+
+        let restricted = Product { limit_only: true, cancel_only: true, ..product.clone() };
+        assert_eq!(restricted.trading_mode(), TradingMode::CancelOnly);
+        let halted = Product { status: serde_json::from_str(r#""paused""#).unwrap(), ..product };
+        assert_eq!(halted.status, ProductStatus::Unknown);
+        assert!(halted.trading_mode().is_restricted());
+    }
+
+    #[test]
+    fn test_validate_order() {
+        let product: Product = serde_json::from_str(include_str!("../tests/fixtures/products_eth-usd.json")).unwrap();
+        let product = Product { base_min_size: Some(Decimal::new(1, 3)), max_market_funds: Some(Decimal::from(1000)), ..product };
+        let price = Some(Decimal::new(130051, 2));
+
+        assert!(product.validate_order(Side::Buy, price, Decimal::new(5, 1)).is_empty());
+        assert!(product.validate_order(Side::Sell, None, Decimal::new(5, 1)).is_empty());
+        assert!(product.validate_order(Side::Buy, None, Decimal::from(20)).is_empty());
+
+        assert_eq!(product.validate_order(Side::Buy, Some(Decimal::new(1300505, 3)), Decimal::new(1, 4)), [
+            OrderViolation::InvalidPrice { price: Decimal::new(1300505, 3), increment: Decimal::new(1, 2) },
+            OrderViolation::BelowMinSize { size: Decimal::new(1, 4), min: Decimal::new(1, 3) },
+        ]);
+        assert_eq!(product.validate_order(Side::Buy, None, Decimal::from(5000)), [
+            OrderViolation::ExceedsMaxFunds { funds: Decimal::from(5000), max: Decimal::from(1000) },
+        ]);
+
+        let limit_only = Product { limit_only: true, ..product };
+        assert_eq!(limit_only.validate_order(Side::Sell, None, Decimal::ONE), [OrderViolation::TradingRestricted(TradingMode::LimitOnly)]);
+        assert!(limit_only.validate_order(Side::Sell, price, Decimal::ONE).is_empty());
+    }
+
+    #[test]
+    fn test_format_order_values() {
+        let product: Product = serde_json::from_str(include_str!("../tests/fixtures/products_eth-usd.json")).unwrap();
+        let product = Product { quote_increment: "0.01000000".parse().unwrap(), ..product };
+
+        assert_eq!(product.format_price("1300.519".parse().unwrap()), "1300.51");
+        assert_eq!(product.format_price(Decimal::from(1300)), "1300.00");
+        assert_eq!(product.format_size(Decimal::from_scientific("1e-7").unwrap()), "0.00000010");
+        assert_eq!(product.format_size(Decimal::new(-15, 10)), "0.00000000");
+        assert_eq!(product.format_funds(Decimal::new(205, 1)), "20.50");
+
+        let coarse = Product { quote_increment: Decimal::new(5, 1), ..product };
+        assert_eq!(coarse.format_price(Decimal::new(10049, 2)), "100.0");
+    }
+
+    #[test]
+    fn test_parse_orderbook() {
+        let book: OrderBookL2 = serde_json::from_str(r#"{
+            "bids": [["1300.50", "1.25", 3], ["1300.49", "0.5", 1]],
+            "asks": [["1300.51", "2", 2]],
+            "sequence": 42339865018, "auction_mode": false, "auction": null
+        }"#).unwrap();
+        assert_eq!(book.best_bid().unwrap().size, Decimal::new(125, 2));
+        assert_eq!(book.best_ask().unwrap().num_orders, 2);
+        assert_eq!(book.spread(), Some(Decimal::new(1, 2)));
+        assert_eq!(book.mid(), Some(Decimal::new(1300505, 3)));
+
+        // 1.75 bid vs 2 ask over two levels, but only the 1.25 best bid is within 0.05bp of mid
+        assert_eq!(book.imbalance(2), Some(Decimal::new(-25, 2) / Decimal::new(375, 2)));
+        assert_eq!(book.imbalance(1), Some(Decimal::new(-75, 2) / Decimal::new(325, 2)));
+        assert_eq!(book.imbalance_within_bps(Decimal::new(5, 2)), book.imbalance(1));
+
+        let (bids, asks) = book.levels_within_bps(Decimal::new(5, 2)).unwrap();
+        assert_eq!((bids.len(), asks.len()), (1, 1));
+        assert_eq!(book.cumulative_size_to(Decimal::new(130049, 2)), Decimal::new(175, 2));
+        assert_eq!(book.cumulative_size_to(Decimal::new(1300505, 3)), Decimal::ZERO);
+        assert_eq!(book.cumulative_size_to(Decimal::from(2000)), Decimal::TWO);
+
+        // selling 1.5 takes 1.25 at 1300.50 and 0.25 at 1300.49
+        let expected = (Decimal::new(125, 2) * Decimal::new(130050, 2) + Decimal::new(25, 2) * Decimal::new(130049, 2))
+            / Decimal::new(15, 1);
+        assert_eq!(book.price_for_size(Side::Sell, Decimal::new(15, 1)), Some(expected));
+        assert_eq!(book.price_for_size(Side::Buy, Decimal::ONE), Some(Decimal::new(130051, 2)));
+        assert!(book.price_for_size(Side::Buy, Decimal::from(3)).is_none());
+
+        let one_sided = OrderBookL2 { asks: Vec::new(), ..book };
+        assert_eq!(one_sided.imbalance(5), Some(Decimal::ONE));
+        assert!(one_sided.spread().is_none() && one_sided.mid().is_none() && one_sided.spread_bps().is_none());
+    }
+
+    #[test]
+    fn test_aggregate_l3() {
+        let book: OrderBookL3 = serde_json::from_str(include_str!("../tests/fixtures/products_eth-usd_book__level=3.json")).unwrap();
+        assert_eq!(book.bids[0].order_id.to_string(), "26bb7dbd-2d1c-4af0-953e-7c2a26a2c0bd");
+
+        let aggregated = book.to_l2(Some(2));
+        assert_eq!(aggregated.sequence, book.sequence);
+        assert_eq!(aggregated.bids.len(), 2);
+        assert_eq!(aggregated.bids[1], BookLevel { price: Decimal::new(129734, 2), size: Decimal::new(411719105, 8), num_orders: 2 });
+
+        let full = OrderBookL2::from(&book);
+        assert_eq!(full.bids.iter().map(|level| level.num_orders).sum::<u64>(), book.bids.len() as u64);
+        assert_eq!(full.asks.iter().map(|level| level.num_orders).sum::<u64>(), book.asks.len() as u64);
+        assert!(full.asks.windows(2).all(|pair| pair[0].price < pair[1].price));
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        let stats: Stats = serde_json::from_str(r#"{
+            "open": "1280.00", "high": "1310.25", "low": "1275.10", "last": "1300.51",
+            "volume": "123456.789", "volume_30day": "4567890.12"
+        }"#).unwrap();
+        assert_eq!(stats.last, Decimal::new(130051, 2));
+    }
+}
@@ -0,0 +1,129 @@
+//! Utilities for comparing and transforming order book snapshots.
+
+// std
+use std::collections::BTreeMap;
+// external
+use rust_decimal::Decimal;
+// crate
+use crate::models::{BookLevel, OrderBookL2};
+
+/// Change to a single price level between two snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelChange {
+    Added(BookLevel),
+    Removed(BookLevel),
+    /// Same price with a different size or order count.
+    Changed { from: BookLevel, to: BookLevel },
+}
+
+impl LevelChange {
+    pub fn price(&self) -> Decimal {
+        match self {
+            LevelChange::Added(level) | LevelChange::Removed(level) => level.price,
+            LevelChange::Changed { to, .. } => to.price,
+        }
+    }
+}
+
+/// Differences between two level 2 snapshots, with each side ordered best price first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookDiff {
+    pub from_sequence: u64,
+    pub to_sequence: u64,
+    pub bids: Vec<LevelChange>,
+    pub asks: Vec<LevelChange>,
+}
+
+impl BookDiff {
+    /// Whether both snapshots have identical levels. Sequence numbers are not compared.
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /// Applies the changes to 'book', which should be the snapshot the diff was taken from.
+    /// Together with diff, this allows storing a series of snapshots as one snapshot and deltas.
+    pub fn apply(&self, book: &OrderBookL2) -> OrderBookL2 {
+        OrderBookL2 {
+            sequence: self.to_sequence,
+            bids: apply_side(&book.bids, &self.bids, true),
+            asks: apply_side(&book.asks, &self.asks, false),
+            extra: book.extra.clone(),
+        }
+    }
+}
+
+/// Computes the levels added, removed and changed from 'old' to 'new'.
+pub fn diff(old: &OrderBookL2, new: &OrderBookL2) -> BookDiff {
+    BookDiff {
+        from_sequence: old.sequence,
+        to_sequence: new.sequence,
+        bids: diff_side(&old.bids, &new.bids, true),
+        asks: diff_side(&old.asks, &new.asks, false),
+    }
+}
+
+fn diff_side(old: &[BookLevel], new: &[BookLevel], descending: bool) -> Vec<LevelChange> {
+    let mut levels: BTreeMap<Decimal, (Option<&BookLevel>, Option<&BookLevel>)> = BTreeMap::new();
+    for level in old {
+        levels.entry(level.price).or_default().0 = Some(level);
+    }
+    for level in new {
+        levels.entry(level.price).or_default().1 = Some(level);
+    }
+
+    let changes = levels.into_values().filter_map(|pair| match pair {
+        (None, Some(to)) => Some(LevelChange::Added(to.clone())),
+        (Some(from), None) => Some(LevelChange::Removed(from.clone())),
+        (Some(from), Some(to)) if from != to => Some(LevelChange::Changed { from: from.clone(), to: to.clone() }),
+        _ => None,
+    });
+    match descending {
+        true => changes.rev().collect(),
+        false => changes.collect(),
+    }
+}
+
+fn apply_side(levels: &[BookLevel], changes: &[LevelChange], descending: bool) -> Vec<BookLevel> {
+    let mut by_price: BTreeMap<Decimal, BookLevel> = levels.iter()
+        .map(|level| (level.price, level.clone()))
+        .collect();
+    for change in changes {
+        match change {
+            LevelChange::Added(level) | LevelChange::Changed { to: level, .. } => {
+                by_price.insert(level.price, level.clone());
+            },
+            LevelChange::Removed(level) => {
+                by_price.remove(&level.price);
+            },
+        }
+    }
+    match descending {
+        true => by_price.into_values().rev().collect(),
+        false => by_price.into_values().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(sequence: u64, bids: &str, asks: &str) -> OrderBookL2 {
+        serde_json::from_str(&format!(r#"{{"sequence": {}, "bids": {}, "asks": {}}}"#, sequence, bids, asks)).unwrap()
+    }
+
+    #[test]
+    fn test_diff_and_apply() {
+        let old = book(1, r#"[["100", "1", 1], ["99", "2", 1]]"#, r#"[["101", "1", 1], ["102", "3", 2]]"#);
+        let new = book(5, r#"[["100.5", "1", 1], ["100", "1", 1], ["99", "4", 2]]"#, r#"[["102", "3", 2]]"#);
+
+        let changes = diff(&old, &new);
+        assert_eq!((changes.from_sequence, changes.to_sequence), (1, 5));
+        assert_eq!(changes.bids.iter().map(LevelChange::price).collect::<Vec<_>>(), [Decimal::new(1005, 1), Decimal::from(99)]);
+        assert!(matches!(changes.bids[0], LevelChange::Added(_)));
+        assert!(matches!(&changes.bids[1], LevelChange::Changed { from, to } if from.size == Decimal::TWO && to.num_orders == 2));
+        assert_eq!(changes.asks, [LevelChange::Removed(old.asks[0].clone())]);
+
+        assert_eq!(changes.apply(&old), new);
+        assert!(diff(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+//! Background polling of public endpoints, for environments where websockets aren't available.
+
+// std
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+// external
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use tokio::sync::broadcast;
+use tokio::time::MissedTickBehavior;
+// crate
+use crate::{CoinbasePublicClient, Granularity, OBLevel};
+use crate::background::{Background, CHANNEL_CAPACITY};
+use crate::candles::{self, GapPolicy};
+use crate::models::{BookLevel, Candle, OrderBookL2, Ticker};
+use crate::orderbook;
+use crate::product_id::{IntoProductId, ProductId};
+
+/// Best bid and ask of a single product, with the time it was fetched.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopOfBook {
+    pub bid: Option<BookLevel>,
+    pub ask: Option<BookLevel>,
+    pub sequence: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TopOfBook {
+    pub fn from_book(book: &OrderBookL2, updated_at: DateTime<Utc>) -> Self {
+        Self {
+            bid: book.best_bid().cloned(),
+            ask: book.best_ask().cloned(),
+            sequence: book.sequence,
+            updated_at,
+        }
+    }
+
+    /// Time elapsed since this quote was fetched.
+    pub fn age(&self) -> chrono::Duration {
+        Utc::now() - self.updated_at
+    }
+
+    /// Whether this quote is older than 'max_age'.
+    pub fn is_stale(&self, max_age: chrono::Duration) -> bool {
+        self.age() > max_age
+    }
+
+    /// Whether both quotes have the same best bid and ask prices and sizes. Order counts,
+    /// sequence numbers and fetch times are ignored.
+    pub fn same_bbo(&self, other: &TopOfBook) -> bool {
+        let price_and_size = |level: &Option<BookLevel>| level.as_ref().map(|level| (level.price, level.size));
+        price_and_size(&self.bid) == price_and_size(&other.bid)
+            && price_and_size(&self.ask) == price_and_size(&other.ask)
+    }
+}
+
+/// Change of best bid or ask price or size for one product.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BboChange {
+    pub product_id: ProductId,
+    /// Quote before the change, or None for the first quote fetched.
+    pub previous: Option<TopOfBook>,
+    pub current: TopOfBook,
+}
+
+/// Cycles through a list of products fetching level 1 books and keeps the latest best bid/ask
+/// of each one. Requests go through the client's rate limiter, so a list of N products refreshes
+/// roughly every N / rate_limit seconds.
+///
+/// A product whose request fails keeps its previous quote, which then shows up as stale.
+/// Dropping the poller stops it.
+#[derive(Debug)]
+pub struct TopOfBookPoller {
+    quotes: Arc<RwLock<HashMap<ProductId, TopOfBook>>>,
+    changes: Background<BboChange>,
+}
+
+impl TopOfBookPoller {
+    /// Starts cycling through the products in a task on the current tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - shared client. Its rate limiter paces the poller.
+    /// * 'product_ids' - market identifiers, either ProductIds or strings formatted as
+    ///   'BASE-QUOTE', such as 'ETH-USD'. Fails without spawning if any of them is malformed.
+    /// * 'min_cycle' - minimum time for one pass over all products. Use Duration::ZERO to poll
+    ///   as fast as the rate limiter allows.
+    pub fn spawn<P: IntoProductId>(
+        client: Arc<CoinbasePublicClient>,
+        product_ids: impl IntoIterator<Item = P>,
+        min_cycle: Duration
+    ) -> Result<Self, anyhow::Error> {
+        let product_ids = product_ids.into_iter()
+            .map(IntoProductId::into_product_id)
+            .collect::<Result<Vec<_>, _>>()?;
+        let quotes: Arc<RwLock<HashMap<ProductId, TopOfBook>>> = Arc::new(RwLock::new(HashMap::new()));
+        let shared_quotes = quotes.clone();
+
+        let changes = Background::spawn(CHANNEL_CAPACITY, |changes| async move {
+            loop {
+                let cycle = tokio::time::sleep(min_cycle);
+                for product_id in &product_ids {
+                    let endpoint = format!("/products/{}/book", product_id);
+                    let params = vec![OBLevel::Level1.param_tuple()];
+                    if let Ok(book) = client.get_typed::<OrderBookL2>(&endpoint, Some(params)).await {
+                        let quote = TopOfBook::from_book(&book, Utc::now());
+                        let previous = shared_quotes.write().unwrap().insert(product_id.clone(), quote.clone());
+                        if !previous.as_ref().is_some_and(|previous| previous.same_bbo(&quote)) {
+                            changes.publish(BboChange { product_id: product_id.clone(), previous, current: quote });
+                        }
+                    }
+                }
+                cycle.await;
+            }
+        });
+
+        Ok(Self { quotes, changes })
+    }
+
+    /// Receives an event whenever a product's best bid or ask price or size changes, including
+    /// its first quote. Polls that return the same top of book emit nothing. Up to 256 events
+    /// are buffered per subscriber.
+    pub fn subscribe(&self) -> broadcast::Receiver<BboChange> {
+        self.changes.subscribe()
+    }
+
+    /// Latest quote for a product, if one has been fetched. Malformed ids never have a quote.
+    pub fn get(&self, product_id: impl IntoProductId) -> Option<TopOfBook> {
+        let product_id = product_id.into_product_id().ok()?;
+        self.quotes.read().unwrap().get(&product_id).cloned()
+    }
+
+    /// Latest quotes for all products fetched so far.
+    pub fn snapshot(&self) -> HashMap<ProductId, TopOfBook> {
+        self.quotes.read().unwrap().clone()
+    }
+}
+
+/// Fetches a product's ticker every 'interval' and yields each one, in place of the websocket
+/// ticker channel. Requests go through the client's rate limiter. Failed requests are skipped,
+/// and if the consumer falls behind, missed ticks are skipped rather than fetched in a burst,
+/// so tickers keep arriving on the original schedule. Must be called inside a tokio runtime.
+///
+/// # Arguments
+///
+/// * 'client' - shared client.
+/// * 'product_id' - market identifier, either a ProductId or a string formatted as
+///   'BASE-QUOTE', such as 'ETH-USD'. Fails if it is malformed.
+/// * 'interval' - time between requests. The first request is sent immediately.
+pub fn poll_ticker(
+    client: Arc<CoinbasePublicClient>,
+    product_id: impl IntoProductId,
+    interval: Duration
+) -> Result<impl Stream<Item = Ticker>, anyhow::Error> {
+    let endpoint = format!("/products/{}/ticker", product_id.into_product_id()?);
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    Ok(futures::stream::unfold((client, endpoint, ticks), |(client, endpoint, mut ticks)| async move {
+        loop {
+            ticks.tick().await;
+            if let Ok(ticker) = client.get_typed::<Ticker>(&endpoint, None).await {
+                return Some((ticker, (client, endpoint, ticks)));
+            }
+        }
+    }))
+}
+
+/// Fetches a product's level 1 or 2 book every 'interval' and yields it whenever any level
+/// changed since the last book yielded, including the first one. Requests go through the
+/// client's rate limiter and failed ones are skipped. Must be called inside a tokio runtime.
+///
+/// # Arguments
+///
+/// * 'client' - shared client.
+/// * 'product_id' - market identifier, either a ProductId or a string formatted as
+///   'BASE-QUOTE', such as 'ETH-USD'. Fails if it is malformed.
+/// * 'level' - Level1 or Level2. Fails for Level3, which has no aggregated levels.
+/// * 'interval' - time between requests. The first request is sent immediately.
+pub fn poll_book(
+    client: Arc<CoinbasePublicClient>,
+    product_id: impl IntoProductId,
+    level: OBLevel,
+    interval: Duration
+) -> Result<impl Stream<Item = OrderBookL2>, anyhow::Error> {
+    if level == OBLevel::Level3 {
+        bail!("level 3 books can't be polled as aggregated levels");
+    }
+    let endpoint = format!("/products/{}/book", product_id.into_product_id()?);
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let state = (client, endpoint, ticks, None::<OrderBookL2>);
+    Ok(futures::stream::unfold(state, move |(client, endpoint, mut ticks, previous)| async move {
+        loop {
+            ticks.tick().await;
+            let Ok(book) = client.get_typed::<OrderBookL2>(&endpoint, Some(vec![level.param_tuple()])).await else {
+                continue;
+            };
+            if !previous.as_ref().is_some_and(|previous| orderbook::diff(previous, &book).is_empty()) {
+                return Some((book.clone(), (client, endpoint, ticks, Some(book))));
+            }
+        }
+    }))
+}
+
+/// Delay before retrying a failed closed candle request. Doubles after each failure.
+const CANDLE_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Attempts at fetching a closed candle before giving up with the error.
+const CANDLE_MAX_ATTEMPTS: u32 = 5;
+
+/// One closed candle period, emitted by poll_candle_closes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedCandle {
+    /// Start of the period.
+    pub time: DateTime<Utc>,
+    /// The period's candle, or None if it had no trades and the gap policy is Empty.
+    pub candle: Option<Candle>,
+}
+
+/// Emits exactly one event per closed candle period, in order, for strategies that act on bar
+/// close. Each period is fetched once it has closed by the server's clock plus 'tolerance',
+/// so trades reported late still make it into the candle. Must be called inside a tokio runtime.
+///
+/// A failed request is retried with backoff, starting a second apart. If it still fails after
+/// CANDLE_MAX_ATTEMPTS (5) attempts, such as for a delisted product, the error is emitted and
+/// the stream ends. To carry on, start a new stream from the failed period.
+///
+/// # Arguments
+///
+/// * 'client' - shared client. Each period is one request through its rate limiter.
+/// * 'product_id' - market identifier, either a ProductId or a string formatted as
+///   'BASE-QUOTE', such as 'ETH-USD'. Fails if it is malformed.
+/// * 'granularity' - candle size.
+/// * 'start' - first period to emit, such as the last one processed before a restart, or None
+///   for the period in progress. Periods that have already closed are emitted straight away.
+/// * 'tolerance' - how long to wait after a period closes before fetching it.
+/// * 'policy' - how to fill periods without trades. CarryForward only fills once a period
+///   with trades has been seen.
+pub fn poll_candle_closes(
+    client: Arc<CoinbasePublicClient>,
+    product_id: impl IntoProductId,
+    granularity: Granularity,
+    start: Option<DateTime<Utc>>,
+    tolerance: Duration,
+    policy: GapPolicy
+) -> Result<impl Stream<Item = Result<ClosedCandle, anyhow::Error>>, anyhow::Error> {
+    let product_id = product_id.into_product_id()?;
+    let step = chrono::Duration::seconds(granularity.seconds() as i64);
+    let tolerance = chrono::Duration::from_std(tolerance)?;
+    let period = candles::bucket_start(start.unwrap_or_else(|| client.server_now()), granularity.seconds() as i64);
+
+    let state = Some((client, product_id, period, None::<Candle>));
+    Ok(futures::stream::unfold(state, move |state| async move {
+        let (client, product_id, period, previous) = state?;
+        if let Ok(wait) = (period + step + tolerance - client.server_now()).to_std() {
+            tokio::time::sleep(wait).await;
+        }
+        let mut delay = CANDLE_RETRY_DELAY;
+        let mut attempts = 1;
+        let fetched = loop {
+            match client.get_candles_chunked(&product_id, period, period, granularity).await {
+                Ok(fetched) => break fetched,
+                Err(err) if attempts == CANDLE_MAX_ATTEMPTS => {
+                    let err = err.context(format!("failed to fetch {} candle at {}", product_id, period));
+                    return Some((Err(err), None));
+                },
+                Err(_) => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempts += 1;
+                },
+            }
+        };
+
+        // the previous candle gives CarryForward a close to fill from
+        let known: Vec<Candle> = previous.into_iter().chain(fetched).collect();
+        let candle = candles::fill_gaps(&known, granularity, period, period, policy).pop().flatten();
+        let previous = candle.clone().or_else(|| known.last().cloned());
+        Some((Ok(ClosedCandle { time: period, candle }), Some((client, product_id, period + step, previous))))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use rust_decimal::Decimal;
+
+    #[tokio::test]
+    async fn test_bbo_changes() {
+        let client = CoinbasePublicClient::builder()
+            .offline(crate::testing::CannedResponses::new()
+                .with("/products/ETH-USD/book", r#"{"bids": [["99", "1", 1]], "asks": [["101", "2", 1]], "sequence": 7}"#))
+            .build();
+        let poller = TopOfBookPoller::spawn(Arc::new(client), ["ETH-USD"], Duration::from_millis(5)).unwrap();
+        let mut changes = poller.subscribe();
+
+        let change = changes.recv().await.unwrap();
+        assert_eq!(change.product_id.as_str(), "ETH-USD");
+        assert!(change.previous.is_none());
+        assert_eq!(change.current.ask.unwrap().price, Decimal::from(101));
+
+        // the book doesn't change, so later polls are silent
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(poller.get("eth-usd").is_some());
+        assert!(matches!(changes.try_recv(), Err(broadcast::error::TryRecvError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn test_poll_ticker() {
+        let client = CoinbasePublicClient::builder()
+            .offline(crate::testing::CannedResponses::new()
+                .with("/products/ETH-USD/ticker", include_str!("../tests/fixtures/products_eth-usd_ticker.json")))
+            .build();
+        assert!(poll_ticker(Arc::new(CoinbasePublicClient::new()), "ETHUSD", Duration::from_secs(1)).is_err());
+
+        let started = tokio::time::Instant::now();
+        let tickers: Vec<Ticker> = poll_ticker(Arc::new(client), "eth-usd", Duration::from_millis(20)).unwrap()
+            .take(3)
+            .collect()
+            .await;
+        assert_eq!(tickers.len(), 3);
+        assert!(tickers.iter().all(|ticker| ticker.bid <= ticker.ask));
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_candle_closes() {
+        let first = candles::bucket_start(Utc::now(), 60) - chrono::Duration::minutes(4);
+        let candle = |minutes: i64, close: i64| format!("[{}, 1, {}, 1, {}, 5]", (first + chrono::Duration::minutes(minutes)).timestamp(), close, close);
+        let client = Arc::new(CoinbasePublicClient::builder()
+            .offline(crate::testing::CannedResponses::new()
+                .with("/products/ETH-USD/candles", format!("[{}, {}]", candle(3, 3), candle(1, 2))))
+            .build());
+
+        let closes = |policy| {
+            poll_candle_closes(client.clone(), "ETH-USD", Granularity::Minute1, Some(first), Duration::ZERO, policy).unwrap()
+                .take(4)
+                .map(|closed| closed.unwrap().candle.map(|candle| candle.close.to_string()))
+                .collect::<Vec<_>>()
+        };
+        // one event per minute, including those without trades
+        let expected = |gap: Option<&str>| vec![None, Some("2".to_owned()), gap.map(str::to_owned), Some("3".to_owned())];
+        assert_eq!(closes(GapPolicy::Empty).await, expected(None));
+        assert_eq!(closes(GapPolicy::CarryForward).await, expected(Some("2")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_candle_closes_give_up() {
+        let client = Arc::new(CoinbasePublicClient::builder()
+            .offline(crate::testing::CannedResponses::new())
+            .build());
+        let first = candles::bucket_start(Utc::now(), 60) - chrono::Duration::minutes(4);
+
+        // no candles endpoint, so every attempt fails and the stream ends with the error
+        let closes: Vec<_> = poll_candle_closes(client, "ETH-USD", Granularity::Minute1, Some(first), Duration::ZERO, GapPolicy::Empty)
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(closes.len(), 1);
+        assert!(closes[0].as_ref().unwrap_err().to_string().contains("ETH-USD"));
+    }
+
+    #[test]
+    fn test_top_of_book_staleness() {
+        let book: OrderBookL2 = serde_json::from_str(
+            r#"{"bids": [["99", "1", 1]], "asks": [["101", "2", 1]], "sequence": 7}"#
+        ).unwrap();
+
+        let fresh = TopOfBook::from_book(&book, Utc::now());
+        assert_eq!(fresh.bid.as_ref().unwrap().price, Decimal::from(99));
+        assert_eq!(fresh.sequence, 7);
+        assert!(!fresh.is_stale(chrono::Duration::seconds(5)));
+
+        let old = TopOfBook::from_book(&book, Utc::now() - chrono::Duration::seconds(10));
+        assert!(old.is_stale(chrono::Duration::seconds(5)));
+        assert!(old.same_bbo(&fresh));
+        assert!(!old.same_bbo(&TopOfBook { ask: None, ..fresh }));
+    }
+}
@@ -0,0 +1,223 @@
+//! Position and PnL tracking from the caller's own fills.
+//!
+//! This client has no authenticated endpoints, so fills come from elsewhere, such as the
+//! fills endpoint or user channel of an authenticated client. Fill deserializes from
+//! Coinbase's fill format.
+
+// std
+use std::collections::{HashMap, HashSet};
+// external
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+// crate
+use crate::models::{OrderId, Side};
+use crate::product_id::ProductId;
+
+/// A single fill of one of the caller's orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub trade_id: u64,
+    pub product_id: ProductId,
+    pub order_id: OrderId,
+    pub created_at: DateTime<Utc>,
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    /// Fee charged, in the quote currency.
+    pub fee: Decimal,
+}
+
+/// Open position in a single product.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    /// Size in the base currency, negative when short.
+    pub size: Decimal,
+    /// Average price paid for the open size, or zero when flat.
+    pub average_entry_price: Decimal,
+    /// Fees paid over every fill, in the quote currency.
+    pub fees: Decimal,
+    /// Profit from closed size over every fill, before fees, in the quote currency.
+    #[serde(default)]
+    pub realized_pnl: Decimal,
+}
+
+impl Position {
+    /// Profit the open size would make if closed at 'mark', such as the current mid price,
+    /// before fees.
+    pub fn unrealized_pnl(&self, mark: Decimal) -> Decimal {
+        self.size * (mark - self.average_entry_price)
+    }
+
+    /// Updates the position and returns the profit realized by the fill, before fees.
+    fn apply(&mut self, fill: &Fill) -> Decimal {
+        let signed_size = match fill.side {
+            Side::Buy => fill.size,
+            Side::Sell => -fill.size,
+        };
+        let new_size = self.size + signed_size;
+
+        let mut realized = Decimal::ZERO;
+        if !self.size.is_zero() && self.size.is_sign_positive() != signed_size.is_sign_positive() {
+            let closed = fill.size.min(self.size.abs());
+            let gain = fill.price - self.average_entry_price;
+            realized = closed * if self.size.is_sign_positive() { gain } else { -gain };
+        }
+
+        if new_size.is_zero() {
+            self.average_entry_price = Decimal::ZERO;
+        } else if self.size.is_zero() || self.size.is_sign_positive() == signed_size.is_sign_positive() {
+            // adding to the position moves the average towards the fill price
+            self.average_entry_price = (self.size.abs() * self.average_entry_price + fill.size * fill.price) / new_size.abs();
+        } else if new_size.is_sign_positive() != self.size.is_sign_positive() {
+            // flipped from long to short or back, so the remainder was opened at the fill price
+            self.average_entry_price = fill.price;
+        }
+        self.size = new_size;
+        self.fees += fill.fee;
+        self.realized_pnl += realized;
+        realized
+    }
+}
+
+/// Profit and loss in the quote currency, of a single product or summed over the products
+/// sharing a quote currency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pnl {
+    pub realized: Decimal,
+    pub unrealized: Decimal,
+    pub fees: Decimal,
+}
+
+impl Pnl {
+    /// Realized plus unrealized, minus fees.
+    pub fn net(&self) -> Decimal {
+        self.realized + self.unrealized - self.fees
+    }
+}
+
+/// Per-product positions built from fills, using average cost.
+///
+/// Fills can come from a REST backfill and a live feed that overlap, since fills already
+/// applied are skipped. Serialize the tracker to save it, and deserialize it at startup to
+/// carry on from where it was.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PositionTracker {
+    positions: HashMap<ProductId, Position>,
+    /// Trade and order id of every fill applied. A trade has one fill per order involved.
+    applied: HashSet<(u64, OrderId)>,
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the fill's product position and returns the profit the fill realized, before
+    /// fees. Returns None if the fill was already applied.
+    pub fn apply(&mut self, fill: &Fill) -> Option<Decimal> {
+        if !self.applied.insert((fill.trade_id, fill.order_id.to_owned())) {
+            return None;
+        }
+        Some(self.positions.entry(fill.product_id.clone()).or_default().apply(fill))
+    }
+
+    /// Position in a product, or None if no fills for it were applied.
+    pub fn position(&self, product_id: &ProductId) -> Option<&Position> {
+        self.positions.get(product_id)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = (&ProductId, &Position)> {
+        self.positions.iter()
+    }
+
+    /// PnL of a single product, with the open size marked at 'mark', or None if no fills for
+    /// it were applied.
+    pub fn pnl(&self, product_id: &ProductId, mark: Decimal) -> Option<Pnl> {
+        self.position(product_id).map(|position| Pnl {
+            realized: position.realized_pnl,
+            unrealized: position.unrealized_pnl(mark),
+            fees: position.fees,
+        })
+    }
+
+    /// PnL summed per quote currency, such as 'USD'.
+    ///
+    /// # Arguments
+    ///
+    /// * 'marks' - price to mark each product at, such as Ticker::mid from
+    ///   'get_market_snapshot'. Only needed for products with an open position.
+    pub fn pnl_by_quote(&self, marks: &HashMap<ProductId, Decimal>) -> Result<HashMap<String, Pnl>, anyhow::Error> {
+        let mut totals: HashMap<String, Pnl> = HashMap::new();
+        for (product_id, position) in &self.positions {
+            let unrealized = match marks.get(product_id) {
+                Some(mark) => position.unrealized_pnl(*mark),
+                None if position.size.is_zero() => Decimal::ZERO,
+                None => return Err(anyhow!("no mark price for open position in {}", product_id)),
+            };
+            let total = totals.entry(product_id.quote().to_owned()).or_default();
+            total.realized += position.realized_pnl;
+            total.unrealized += unrealized;
+            total.fees += position.fees;
+        }
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(trade_id: u64, side: &str, price: i64, size: i64) -> Fill {
+        serde_json::from_value(serde_json::json!({
+            "created_at": "2022-10-10T10:00:00Z", "trade_id": trade_id, "product_id": "ETH-USD",
+            "order_id": format!("00000000-0000-0000-0000-{:012}", trade_id), "user_id": "user", "profile_id": "profile",
+            "liquidity": "T", "price": price.to_string(), "size": size.to_string(), "fee": "0.5",
+            "side": side, "settled": true, "usd_volume": "0",
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_positions_from_fills() {
+        let product_id: ProductId = "ETH-USD".parse().unwrap();
+        let mut tracker = PositionTracker::new();
+        assert_eq!(tracker.apply(&fill(1, "buy", 100, 1)), Some(Decimal::ZERO));
+        assert!(tracker.apply(&fill(2, "buy", 130, 2)).is_some());
+        assert!(tracker.apply(&fill(2, "buy", 130, 2)).is_none());
+
+        let position = tracker.position(&product_id).unwrap();
+        assert_eq!((position.size, position.average_entry_price, position.fees), (Decimal::from(3), Decimal::from(120), Decimal::ONE));
+
+        // reducing keeps the average, flipping starts a new one
+        tracker.apply(&fill(3, "sell", 150, 1));
+        assert_eq!(tracker.position(&product_id).unwrap().average_entry_price, Decimal::from(120));
+        tracker.apply(&fill(4, "sell", 140, 4));
+        let position = tracker.position(&product_id).unwrap();
+        assert_eq!((position.size, position.average_entry_price), (Decimal::from(-2), Decimal::from(140)));
+
+        let saved = serde_json::to_string(&tracker).unwrap();
+        let mut restored: PositionTracker = serde_json::from_str(&saved).unwrap();
+        assert_eq!(restored, tracker);
+        assert!(restored.apply(&fill(1, "buy", 100, 1)).is_none());
+    }
+
+    #[test]
+    fn test_pnl() {
+        let (eth, btc): (ProductId, ProductId) = ("ETH-USD".parse().unwrap(), "BTC-USD".parse().unwrap());
+        let mut tracker = PositionTracker::new();
+        tracker.apply(&fill(1, "buy", 100, 2));
+        // sells 2 at 110 to close, then 1 more to open a short
+        assert_eq!(tracker.apply(&fill(2, "sell", 110, 3)), Some(Decimal::from(20)));
+        assert_eq!(tracker.apply(&fill(3, "buy", 120, 1)), Some(Decimal::from(-10)));
+        tracker.apply(&Fill { product_id: btc.clone(), ..fill(4, "buy", 1000, 1) });
+
+        let pnl = tracker.pnl(&eth, Decimal::from(150)).unwrap();
+        assert_eq!(pnl, Pnl { realized: Decimal::from(10), unrealized: Decimal::ZERO, fees: Decimal::new(15, 1) });
+
+        assert!(tracker.pnl_by_quote(&HashMap::new()).is_err());
+        let totals = tracker.pnl_by_quote(&HashMap::from([(btc, Decimal::from(1100))])).unwrap();
+        assert_eq!(totals["USD"].unrealized, Decimal::from(100));
+        assert_eq!(totals["USD"].net(), Decimal::from(108));
+    }
+}
@@ -0,0 +1,189 @@
+//! Streams of reference prices derived from tickers and books, for signal code.
+
+// std
+use std::pin::Pin;
+use std::time::Duration;
+// external
+use futures::{future, stream::Fuse, Stream, StreamExt};
+use rust_decimal::Decimal;
+use tokio::time::Instant;
+// crate
+use crate::models::{OrderBookL2, Ticker};
+use crate::poller::{BboChange, TopOfBook};
+
+/// Anything carrying a best bid and ask to take a mid price from.
+pub trait MidPrice {
+    /// Halfway between best bid and best ask, or None if either is missing.
+    fn mid_price(&self) -> Option<Decimal>;
+}
+
+impl MidPrice for Ticker {
+    fn mid_price(&self) -> Option<Decimal> {
+        Some(self.mid())
+    }
+}
+
+impl MidPrice for OrderBookL2 {
+    fn mid_price(&self) -> Option<Decimal> {
+        self.mid()
+    }
+}
+
+impl MidPrice for TopOfBook {
+    fn mid_price(&self) -> Option<Decimal> {
+        Some((self.bid.as_ref()?.price + self.ask.as_ref()?.price) / Decimal::TWO)
+    }
+}
+
+impl MidPrice for BboChange {
+    fn mid_price(&self) -> Option<Decimal> {
+        self.current.mid_price()
+    }
+}
+
+/// Mid prices of a stream of quotes, such as poll_ticker or TopOfBookPoller changes. Quotes
+/// without a mid price are skipped.
+///
+/// # Arguments
+///
+/// * 'quotes' - tickers, books or top of book quotes of a single product.
+/// * 'throttle' - minimum time between mid prices. Mid prices arriving sooner are held back,
+///   and the newest of them is emitted once the throttle has passed, so the last mid price
+///   emitted is never staler than the throttle.
+/// * 'dedup' - whether to drop mid prices equal to the last one emitted.
+pub fn mid_prices<S>(quotes: S, throttle: Option<Duration>, dedup: bool) -> impl Stream<Item = Decimal>
+where
+    S: Stream,
+    S::Item: MidPrice,
+{
+    let state = Throttled { quotes: Box::pin(quotes.fuse()), last: None, pending: None };
+    futures::stream::unfold(state, move |mut state| async move {
+        loop {
+            let deadline = state.last.zip(throttle).map(|((_, emitted), throttle)| emitted + throttle);
+            let quote = match (state.pending, deadline) {
+                (Some(pending), Some(deadline)) => tokio::select! {
+                    quote = state.quotes.next() => quote,
+                    _ = tokio::time::sleep_until(deadline) => {
+                        state.pending = None;
+                        return Some((state.emit(pending), state));
+                    },
+                },
+                _ => state.quotes.next().await,
+            };
+            let Some(quote) = quote else {
+                // held back mid prices still go out once the throttle passes
+                let pending = state.pending.take()?;
+                if let Some(deadline) = deadline {
+                    tokio::time::sleep_until(deadline).await;
+                }
+                return Some((state.emit(pending), state));
+            };
+            let Some(mid) = quote.mid_price() else {
+                continue;
+            };
+
+            let last_mid = state.last.map(|(mid, _)| mid);
+            if deadline.is_some_and(|deadline| Instant::now() < deadline) {
+                state.pending = (!dedup || last_mid != Some(mid)).then_some(mid);
+            } else if !dedup || last_mid != Some(mid) {
+                state.pending = None;
+                return Some((state.emit(mid), state));
+            }
+        }
+    })
+}
+
+/// State of mid_prices between items.
+struct Throttled<S> {
+    quotes: Pin<Box<Fuse<S>>>,
+    /// Last mid price emitted and when.
+    last: Option<(Decimal, Instant)>,
+    /// Newest mid price held back by the throttle.
+    pending: Option<Decimal>,
+}
+
+impl<S> Throttled<S> {
+    fn emit(&mut self, mid: Decimal) -> Decimal {
+        self.last = Some((mid, Instant::now()));
+        mid
+    }
+}
+
+/// Depth-weighted mid prices of a stream of books, such as poll_book, which only yields books
+/// that changed. Books too thin to fill 'size' on both sides are skipped, as are prices equal
+/// to the last one emitted.
+///
+/// # Arguments
+///
+/// * 'books' - level 2 books of a single product.
+/// * 'size' - depth to average over on each side, in the base currency. See
+///   OrderBookL2::depth_weighted_mid.
+pub fn depth_weighted_prices<S>(books: S, size: Decimal) -> impl Stream<Item = Decimal>
+where
+    S: Stream<Item = OrderBookL2>,
+{
+    let mut last: Option<Decimal> = None;
+    books.filter_map(move |book| {
+        let price = book.depth_weighted_mid(size).filter(|&price| last != Some(price));
+        if price.is_some() {
+            last = price;
+        }
+        future::ready(price)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(bid: i64, ask: i64) -> OrderBookL2 {
+        serde_json::from_value(serde_json::json!({
+            "bids": [[bid.to_string(), "1", 1]], "asks": [[ask.to_string(), "1", 1]], "sequence": 1,
+        })).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mid_prices() {
+        let books = || futures::stream::iter([book(99, 101), book(98, 102), book(99, 103), OrderBookL2 { asks: vec![], ..book(1, 2) }]);
+        let all: Vec<Decimal> = mid_prices(books(), None, false).collect().await;
+        assert_eq!(all, [Decimal::from(100), Decimal::from(100), Decimal::from(101)]);
+
+        let deduped: Vec<Decimal> = mid_prices(books(), None, true).collect().await;
+        assert_eq!(deduped, [Decimal::from(100), Decimal::from(101)]);
+
+        // the 100 and 101 inside the window are held back, and the newest goes out after it
+        let started = Instant::now();
+        let throttled: Vec<Decimal> = mid_prices(books(), Some(Duration::from_secs(60)), false).collect().await;
+        assert_eq!(throttled, [Decimal::from(100), Decimal::from(101)]);
+        assert!(started.elapsed() >= Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_mid_trails_quiet_stream() {
+        // the price moves inside the window and then nothing more arrives
+        let quotes = futures::stream::iter([book(99, 101), book(101, 103)]).chain(futures::stream::pending());
+        let mut mids = Box::pin(mid_prices(quotes, Some(Duration::from_secs(5)), true));
+
+        assert_eq!(mids.next().await, Some(Decimal::from(100)));
+        let started = Instant::now();
+        assert_eq!(mids.next().await, Some(Decimal::from(102)));
+        assert!(started.elapsed() >= Duration::from_secs(5));
+        assert!(tokio::time::timeout(Duration::from_secs(60), mids.next()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_depth_weighted_prices() {
+        let client = crate::CoinbasePublicClient::builder()
+            .offline(crate::testing::CannedResponses::new().with("/products/ETH-USD/book", r#"{
+                "bids": [["99", "1", 1], ["97", "3", 2]], "asks": [["101", "2", 1], ["105", "2", 1]], "sequence": 7
+            }"#))
+            .build();
+        let books = crate::poller::poll_book(std::sync::Arc::new(client), "ETH-USD", crate::OBLevel::Level2, Duration::from_millis(5)).unwrap();
+        let mut prices = Box::pin(depth_weighted_prices(books, Decimal::TWO));
+
+        // buying 2 fills at 101, selling 2 averages 98
+        assert_eq!(prices.next().await, Some(Decimal::new(995, 1)));
+        // the book never changes, so nothing more is emitted
+        assert!(tokio::time::timeout(Duration::from_millis(30), prices.next()).await.is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Parsing and normalization of market identifiers.
+
+// std
+use std::borrow::{Borrow, Cow};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+// external
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+// crate
+use crate::models::Product;
+
+/// Separators accepted between base and quote currencies when parsing.
+const SEPARATORS: [char; 4] = ['-', '/', '_', ':'];
+
+/// Market identifier in Coinbase's canonical 'BASE-QUOTE' format, such as 'ETH-USD'.
+///
+/// Parsing accepts other common spellings like 'eth/usd' or 'ETH_USD'. Symbols without a
+/// separator, like 'ETHUSD', are ambiguous on their own and must be resolved against the
+/// product list with ProductId::resolve.
+///
+/// Client methods accept anything implementing IntoProductId, so plain strings still work and
+/// malformed ids are rejected before any request is sent.
+#[derive(Debug, Clone)]
+pub struct ProductId {
+    id: Cow<'static, str>,
+    separator: usize,
+}
+
+impl ProductId {
+    /// Builds a product id from base and quote currency codes.
+    pub fn new(base: &str, quote: &str) -> Result<Self, anyhow::Error> {
+        let (base, quote) = (base.trim(), quote.trim());
+        for code in [base, quote] {
+            if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+                bail!("invalid currency code '{}'", code);
+            }
+        }
+        Ok(Self {
+            id: Cow::Owned(format!("{}-{}", base.to_ascii_uppercase(), quote.to_ascii_uppercase())),
+            separator: base.len(),
+        })
+    }
+
+    /// Parses any accepted spelling, falling back to matching separator-less symbols
+    /// like 'ETHUSD' against the given products.
+    pub fn resolve(symbol: &str, products: &[Product]) -> Result<Self, anyhow::Error> {
+        if symbol.contains(SEPARATORS) {
+            return symbol.parse();
+        }
+        let symbol = symbol.trim().to_ascii_uppercase();
+        let mut matches = products.iter().filter(|product| {
+            symbol.len() == product.base_currency.len() + product.quote_currency.len()
+                && symbol.starts_with(&product.base_currency)
+                && symbol.ends_with(&product.quote_currency)
+        });
+        match (matches.next(), matches.next()) {
+            (Some(product), None) => Self::new(&product.base_currency, &product.quote_currency),
+            (Some(_), Some(_)) => bail!("symbol '{}' matches more than one product", symbol),
+            (None, _) => bail!("symbol '{}' does not match any product", symbol),
+        }
+    }
+
+    /// Product id from a canonical 'BASE-QUOTE' literal, for constants. Fails to compile if
+    /// 'id' has no '-'.
+    #[cfg(feature = "products")]
+    pub(crate) const fn from_static(id: &'static str) -> Self {
+        let bytes = id.as_bytes();
+        let mut separator = 0;
+        while bytes[separator] != b'-' {
+            separator += 1;
+        }
+        Self { id: Cow::Borrowed(id), separator }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+
+    pub fn base(&self) -> &str {
+        &self.id[..self.separator]
+    }
+
+    pub fn quote(&self) -> &str {
+        &self.id[self.separator + 1..]
+    }
+}
+
+// comparisons only look at the id, so that Borrow<str> is consistent with Hash and Eq
+impl PartialEq for ProductId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ProductId {}
+
+impl Hash for ProductId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl PartialOrd for ProductId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ProductId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl Borrow<str> for ProductId {
+    fn borrow(&self) -> &str {
+        &self.id
+    }
+}
+
+impl FromStr for ProductId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (base, quote) = s.split_once(SEPARATORS)
+            .ok_or_else(|| anyhow!("product id '{}' has no separator; use ProductId::resolve", s))?;
+        Self::new(base, quote)
+    }
+}
+
+impl fmt::Display for ProductId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+impl AsRef<str> for ProductId {
+    fn as_ref(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Serializes as the canonical 'BASE-QUOTE' string.
+impl Serialize for ProductId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.id)
+    }
+}
+
+/// Deserializes from any spelling FromStr accepts.
+impl<'de> Deserialize<'de> for ProductId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(D::Error::custom)
+    }
+}
+
+impl From<ProductId> for String {
+    fn from(product_id: ProductId) -> Self {
+        product_id.id.into_owned()
+    }
+}
+
+impl From<&ProductId> for ProductId {
+    fn from(product_id: &ProductId) -> Self {
+        product_id.clone()
+    }
+}
+
+impl TryFrom<&str> for ProductId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for ProductId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<&String> for ProductId {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Anything that can be validated into a ProductId: a ProductId itself, or a string in any
+/// spelling accepted by ProductId's FromStr, such as "ETH-USD" or "eth/usd".
+pub trait IntoProductId {
+    fn into_product_id(self) -> Result<ProductId, anyhow::Error>;
+}
+
+impl<T> IntoProductId for T
+where
+    T: TryInto<ProductId>,
+    anyhow::Error: From<T::Error>,
+{
+    fn into_product_id(self) -> Result<ProductId, anyhow::Error> {
+        Ok(self.try_into()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spellings() {
+        for symbol in ["ETH-USD", "eth/usd", "Eth_Usd", " eth:usd "] {
+            let product_id: ProductId = symbol.parse().unwrap();
+            assert_eq!(product_id.as_str(), "ETH-USD");
+            assert_eq!((product_id.base(), product_id.quote()), ("ETH", "USD"));
+        }
+        assert!("ETHUSD".parse::<ProductId>().is_err());
+        assert!("ETH-".parse::<ProductId>().is_err());
+        assert!("ETH-US$".parse::<ProductId>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_against_products() {
+        let products: Vec<Product> = serde_json::from_str(r#"[
+            {"id": "ETH-USD", "base_currency": "ETH", "quote_currency": "USD", "quote_increment": "0.01",
+             "base_increment": "0.00000001", "display_name": "ETH/USD", "status": "online"},
+            {"id": "ETH-USDT", "base_currency": "ETH", "quote_currency": "USDT", "quote_increment": "0.01",
+             "base_increment": "0.00000001", "display_name": "ETH/USDT", "status": "online"}
+        ]"#).unwrap();
+
+        assert_eq!(ProductId::resolve("ethusd", &products).unwrap().as_str(), "ETH-USD");
+        assert_eq!(ProductId::resolve("ETHUSDT", &products).unwrap().quote(), "USDT");
+        assert_eq!(ProductId::resolve("eth/usd", &products).unwrap().as_str(), "ETH-USD");
+        assert!(ProductId::resolve("BTCUSD", &products).is_err());
+    }
+
+    #[test]
+    fn test_into_product_id() {
+        let expected: ProductId = "ETH-USD".parse().unwrap();
+        assert_eq!("eth-usd".into_product_id().unwrap(), expected);
+        assert_eq!(String::from("ETH/USD").into_product_id().unwrap(), expected);
+        assert_eq!((&expected).into_product_id().unwrap(), expected);
+        assert!("ETHUSD".into_product_id().is_err());
+
+        let mut set = std::collections::HashSet::new();
+        set.insert(expected);
+        assert!(set.contains("ETH-USD"));
+    }
+}
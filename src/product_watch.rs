@@ -0,0 +1,185 @@
+//! Background polling of the product list for listings, delistings and trading status changes.
+
+// std
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+// external
+use tokio::sync::broadcast;
+// crate
+use crate::CoinbasePublicClient;
+use crate::background::{Background, CHANNEL_CAPACITY};
+use crate::models::{Product, ProductStatus, TradingMode};
+use crate::watcher::Watcher;
+
+/// Change to a product between two polls of the product list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProductEvent {
+    /// A product appeared in the list.
+    Listed(Product),
+    /// A product's status changed to delisted, or it disappeared from the list, in which case
+    /// this holds its last known metadata.
+    Delisted(Product),
+    /// The product's trading mode changed, for example from Normal to CancelOnly. The new
+    /// mode is product.trading_mode().
+    TradingModeChanged { product: Product, previous: TradingMode },
+}
+
+/// Compares a product list against the previous one, keyed by product id, and returns the
+/// resulting events in the order of 'current', followed by products that disappeared,
+/// sorted by id.
+pub fn product_events(previous: &HashMap<String, Product>, current: &[Product]) -> Vec<ProductEvent> {
+    let mut events = Vec::new();
+    for product in current {
+        let Some(old) = previous.get(&product.id) else {
+            events.push(ProductEvent::Listed(product.clone()));
+            continue;
+        };
+        if product.status == ProductStatus::Delisted && old.status != ProductStatus::Delisted {
+            events.push(ProductEvent::Delisted(product.clone()));
+        }
+        if old.trading_mode() != product.trading_mode() {
+            events.push(ProductEvent::TradingModeChanged { product: product.clone(), previous: old.trading_mode() });
+        }
+    }
+
+    let mut removed: Vec<&Product> = previous.values()
+        .filter(|old| !current.iter().any(|product| product.id == old.id))
+        .collect();
+    removed.sort_by(|a, b| a.id.cmp(&b.id));
+    events.extend(removed.into_iter().cloned().map(ProductEvent::Delisted));
+
+    events
+}
+
+/// Polls the product list at a fixed interval and emits an event whenever a product is listed,
+/// delisted or changes trading mode, so bots can pick up new markets and stop quoting as soon
+/// as a market enters a restricted mode.
+///
+/// Built on a Watcher of the product list, with product_events comparing each changed list
+/// against the previous one. The first list fetched only records the current state. A failed
+/// poll is skipped and compared against on the next one. Polling stops when the watcher is
+/// dropped.
+#[derive(Debug)]
+pub struct ProductWatcher {
+    products: Watcher<Vec<Product>>,
+    events: Background<ProductEvent>,
+}
+
+impl ProductWatcher {
+    /// Starts watching. Must be called inside a tokio runtime.
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - shared client. Each poll is one request through its rate limiter.
+    /// * 'interval' - time between polls.
+    pub fn spawn(client: Arc<CoinbasePublicClient>, interval: Duration) -> Self {
+        let products: Watcher<Vec<Product>> = Watcher::spawn(client, "/products", interval);
+        // a list fetched before subscribing shows up in latest, and one fetched after arrives
+        // on the channel too, where it compares equal to itself
+        let mut changes = products.subscribe();
+        let initial = products.latest();
+
+        let events = Background::spawn(CHANNEL_CAPACITY, |events| async move {
+            let by_id = |products: Vec<Product>| -> HashMap<String, Product> {
+                products.into_iter().map(|product| (product.id.clone(), product)).collect()
+            };
+            let mut known = initial.map(by_id);
+            loop {
+                let products = match changes.recv().await {
+                    Ok(products) => products,
+                    // lists still buffered are compared against the last one seen
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                if let Some(previous) = &known {
+                    for event in product_events(previous, &products) {
+                        events.publish(event);
+                    }
+                }
+                known = Some(by_id(products));
+            }
+        });
+
+        Self { products, events }
+    }
+
+    /// Receives product events. Falling more than 256 events behind skips the oldest, with a
+    /// Lagged error.
+    pub fn subscribe(&self) -> broadcast::Receiver<ProductEvent> {
+        self.events.subscribe()
+    }
+
+    /// Product list as of the last successful poll, if any.
+    pub fn products(&self) -> Option<Vec<Product>> {
+        self.products.latest()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::transport::{HttpTransport, TransportFuture};
+
+    fn products() -> Vec<Product> {
+        serde_json::from_str(include_str!("../tests/fixtures/products.json")).unwrap()
+    }
+
+    #[test]
+    fn test_trading_mode_changes() {
+        let previous: HashMap<String, Product> = products().into_iter()
+            .map(|product| (product.id.clone(), product))
+            .collect();
+        assert!(product_events(&previous, &products()).is_empty());
+
+        let mut current = products();
+        current[0].cancel_only = true;
+        let events = product_events(&previous, &current);
+        assert_eq!(events, [ProductEvent::TradingModeChanged { product: current[0].clone(), previous: TradingMode::Normal }]);
+    }
+
+    #[test]
+    fn test_listings_and_delistings() {
+        let mut current = products();
+        let removed = current.remove(1);
+        let listed = Product { id: "NEW-USD".to_owned(), base_currency: "NEW".to_owned(), ..current[0].clone() };
+        current.push(listed.clone());
+        current[0].status = ProductStatus::Delisted;
+
+        let previous: HashMap<String, Product> = products().into_iter()
+            .map(|product| (product.id.clone(), product))
+            .collect();
+        let events = product_events(&previous, &current);
+        assert_eq!(events, [
+            ProductEvent::Delisted(current[0].clone()),
+            ProductEvent::TradingModeChanged { product: current[0].clone(), previous: TradingMode::Normal },
+            ProductEvent::Listed(listed),
+            ProductEvent::Delisted(removed),
+        ]);
+    }
+
+    /// Serves the fixture product list, without its second product after the first poll.
+    struct Delisting(AtomicUsize);
+
+    impl HttpTransport for Delisting {
+        fn send(&self, _request: http::Request<()>) -> TransportFuture<'_> {
+            let mut current = products();
+            if self.0.fetch_add(1, Ordering::SeqCst) > 0 {
+                current.remove(1);
+            }
+            let body = serde_json::to_vec(&current).unwrap();
+            Box::pin(async { Ok(http::Response::new(body)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watcher_emits_delisting() {
+        let client = Arc::new(CoinbasePublicClient::builder().transport(Delisting(AtomicUsize::new(0))).rate_limit(0).build());
+        let watcher = ProductWatcher::spawn(client, Duration::from_millis(5));
+        let mut events = watcher.subscribe();
+
+        assert_eq!(events.recv().await.unwrap(), ProductEvent::Delisted(products().remove(1)));
+        assert_eq!(watcher.products().unwrap().len(), products().len() - 1);
+    }
+}
@@ -0,0 +1,62 @@
+//! ProductId constants for major markets, so typos in product ids are compile errors.
+//! Requires the 'products' feature.
+//!
+//! The list is generated with 'cb product-constants', which prints an entry for every online
+//! market quoted in the given currencies. Paste its output into the product_constants
+//! invocation below to refresh it. Markets can be delisted after the list was generated, so
+//! a constant existing doesn't mean the market is online.
+
+// crate
+use crate::product_id::ProductId;
+
+macro_rules! product_constants {
+    ($($name:ident = $id:literal),* $(,)?) => {
+        $(
+            #[doc = concat!("The ", $id, " market.")]
+            pub const $name: ProductId = ProductId::from_static($id);
+        )*
+
+        /// Every product with a constant, in alphabetical order.
+        pub const ALL: &[ProductId] = &[$($name,)*];
+    };
+}
+
+product_constants! {
+    ADA_USD = "ADA-USD",
+    ATOM_USD = "ATOM-USD",
+    AVAX_USD = "AVAX-USD",
+    BCH_USD = "BCH-USD",
+    BTC_EUR = "BTC-EUR",
+    BTC_GBP = "BTC-GBP",
+    BTC_USD = "BTC-USD",
+    BTC_USDT = "BTC-USDT",
+    DOGE_USD = "DOGE-USD",
+    DOT_USD = "DOT-USD",
+    ETH_BTC = "ETH-BTC",
+    ETH_EUR = "ETH-EUR",
+    ETH_GBP = "ETH-GBP",
+    ETH_USD = "ETH-USD",
+    ETH_USDT = "ETH-USDT",
+    LINK_USD = "LINK-USD",
+    LTC_USD = "LTC-USD",
+    MATIC_USD = "MATIC-USD",
+    SOL_USD = "SOL-USD",
+    UNI_USD = "UNI-USD",
+    USDT_USD = "USDT-USD",
+    XLM_USD = "XLM-USD",
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants_match_parsed_ids() {
+        assert_eq!(BTC_USD, "btc/usd".parse::<ProductId>().unwrap());
+        assert_eq!((ETH_USDT.base(), ETH_USDT.quote()), ("ETH", "USDT"));
+        for product_id in ALL {
+            assert_eq!(*product_id, product_id.as_str().parse::<ProductId>().unwrap());
+        }
+        assert!(ALL.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
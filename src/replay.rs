@@ -0,0 +1,327 @@
+//! Offline reconstruction of level 3 order books from recorded full channel messages.
+//!
+//! A replay starts from a level 3 snapshot and applies the messages that follow it, so the
+//! book can be rebuilt exactly as it was at any sequence number or time. Messages are read
+//! from json lines, one websocket message per line, in the format Coinbase sends them.
+
+// std
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::io::BufRead;
+// external
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+// crate
+use crate::models::{BookOrder, OrderBookL3, OrderId, Side};
+
+/// A message from the full channel. Only the fields needed to maintain the book are kept.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FullMessage {
+    /// An order was accepted by the matching engine. It doesn't rest on the book yet.
+    Received {
+        sequence: u64,
+        time: DateTime<Utc>,
+        order_id: OrderId,
+        side: Side,
+    },
+    /// The remainder of an order was placed on the book.
+    Open {
+        sequence: u64,
+        time: DateTime<Utc>,
+        order_id: OrderId,
+        side: Side,
+        price: Decimal,
+        remaining_size: Decimal,
+    },
+    /// An order left the book, or never rested on it, because it was filled or canceled.
+    Done {
+        sequence: u64,
+        time: DateTime<Utc>,
+        order_id: OrderId,
+        side: Side,
+        reason: String,
+    },
+    /// A taker order traded against a resting maker order.
+    Match {
+        sequence: u64,
+        time: DateTime<Utc>,
+        trade_id: u64,
+        maker_order_id: OrderId,
+        taker_order_id: OrderId,
+        side: Side,
+        price: Decimal,
+        size: Decimal,
+    },
+    /// A resting order's size was changed.
+    Change {
+        sequence: u64,
+        time: DateTime<Utc>,
+        order_id: OrderId,
+        side: Side,
+        #[serde(default)]
+        new_size: Option<Decimal>,
+    },
+    /// Any other message, such as subscriptions or heartbeats. Skipped during replay.
+    #[serde(other)]
+    Other,
+}
+
+impl FullMessage {
+    /// Sequence number of the message, or None for messages that don't affect the book.
+    pub fn sequence(&self) -> Option<u64> {
+        match self {
+            FullMessage::Received { sequence, .. }
+            | FullMessage::Open { sequence, .. }
+            | FullMessage::Done { sequence, .. }
+            | FullMessage::Match { sequence, .. }
+            | FullMessage::Change { sequence, .. } => Some(*sequence),
+            FullMessage::Other => None,
+        }
+    }
+
+    /// Time the matching engine processed the message, or None for messages that don't
+    /// affect the book.
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        match self {
+            FullMessage::Received { time, .. }
+            | FullMessage::Open { time, .. }
+            | FullMessage::Done { time, .. }
+            | FullMessage::Match { time, .. }
+            | FullMessage::Change { time, .. } => Some(*time),
+            FullMessage::Other => None,
+        }
+    }
+}
+
+/// Reads recorded messages from json lines, one message per line. Blank lines are skipped.
+pub fn read_messages<R: BufRead>(reader: R) -> impl Iterator<Item = Result<FullMessage, anyhow::Error>> {
+    reader.lines().enumerate().filter_map(|(i, line)| {
+        let line = match line.context("failed to read recorded messages") {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err)),
+        };
+        if line.trim().is_empty() {
+            return None;
+        }
+        Some(serde_json::from_str(&line).with_context(|| format!("failed to parse message on line {}", i + 1)))
+    })
+}
+
+/// Error returned when a message's sequence number skips ahead, meaning messages are missing
+/// from the recording. Retrieve it with anyhow's downcast_ref.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceGap {
+    pub expected: u64,
+    pub received: u64,
+}
+
+impl fmt::Display for SequenceGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected message {} but received {}", self.expected, self.received)
+    }
+}
+
+impl std::error::Error for SequenceGap {}
+
+/// Level 3 book rebuilt by applying full channel messages to a snapshot.
+#[derive(Debug, Clone)]
+pub struct L3Replay {
+    sequence: u64,
+    time: Option<DateTime<Utc>>,
+    bids: BTreeMap<Decimal, VecDeque<BookOrder>>,
+    asks: BTreeMap<Decimal, VecDeque<BookOrder>>,
+    /// Side and price of every resting order, by order id.
+    orders: HashMap<OrderId, (Side, Decimal)>,
+}
+
+impl L3Replay {
+    /// Starts from a level 3 snapshot, such as one returned by the book endpoint while the
+    /// messages were being recorded.
+    pub fn new(snapshot: &OrderBookL3) -> Self {
+        let mut replay = Self {
+            sequence: snapshot.sequence,
+            time: None,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            orders: HashMap::new(),
+        };
+        for order in &snapshot.bids {
+            replay.insert(Side::Buy, order.clone());
+        }
+        for order in &snapshot.asks {
+            replay.insert(Side::Sell, order.clone());
+        }
+        replay
+    }
+
+    /// Sequence number of the last message applied, or of the snapshot if none were.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Time of the last message applied, or None if none were.
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        self.time
+    }
+
+    /// Applies a message to the book. Returns false if it was skipped because the book
+    /// already includes it, or it doesn't affect the book. Fails with SequenceGap if messages
+    /// are missing, leaving the book unchanged.
+    pub fn apply(&mut self, message: &FullMessage) -> Result<bool, anyhow::Error> {
+        let Some(sequence) = message.sequence() else {
+            return Ok(false);
+        };
+        if sequence <= self.sequence {
+            return Ok(false);
+        }
+        if sequence != self.sequence + 1 {
+            return Err(SequenceGap { expected: self.sequence + 1, received: sequence }.into());
+        }
+
+        match message {
+            FullMessage::Open { order_id, side, price, remaining_size, .. } => {
+                self.insert(*side, BookOrder { price: *price, size: *remaining_size, order_id: order_id.to_owned() });
+            },
+            FullMessage::Done { order_id, .. } => self.remove(order_id),
+            FullMessage::Match { maker_order_id, size, .. } => {
+                // filled makers stay on the book until their done message
+                if let Some(order) = self.find_mut(maker_order_id) {
+                    order.size -= *size;
+                }
+            },
+            FullMessage::Change { order_id, new_size: Some(new_size), .. } => {
+                if let Some(order) = self.find_mut(order_id) {
+                    order.size = *new_size;
+                }
+            },
+            _ => {},
+        }
+        self.sequence = sequence;
+        self.time = message.time();
+        Ok(true)
+    }
+
+    /// Applies messages up to and including 'until', stopping before the first message
+    /// after it. Messages must be in sequence order.
+    pub fn replay_until<'a, I>(&mut self, messages: I, until: DateTime<Utc>) -> Result<(), anyhow::Error>
+    where
+        I: IntoIterator<Item = &'a FullMessage>,
+    {
+        for message in messages {
+            if message.time().is_some_and(|time| time > until) {
+                break;
+            }
+            self.apply(message)?;
+        }
+        Ok(())
+    }
+
+    /// Current state of the book, with each price level's orders in queue priority.
+    pub fn book(&self) -> OrderBookL3 {
+        OrderBookL3 {
+            sequence: self.sequence,
+            bids: self.bids.values().rev().flatten().cloned().collect(),
+            asks: self.asks.values().flatten().cloned().collect(),
+            extra: HashMap::new(),
+        }
+    }
+
+    fn levels(&mut self, side: Side) -> &mut BTreeMap<Decimal, VecDeque<BookOrder>> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+
+    fn insert(&mut self, side: Side, order: BookOrder) {
+        self.orders.insert(order.order_id.to_owned(), (side, order.price));
+        self.levels(side).entry(order.price).or_default().push_back(order);
+    }
+
+    fn remove(&mut self, order_id: &OrderId) {
+        let Some((side, price)) = self.orders.remove(order_id) else {
+            return;
+        };
+        let levels = self.levels(side);
+        if let Some(queue) = levels.get_mut(&price) {
+            queue.retain(|order| order.order_id != *order_id);
+            if queue.is_empty() {
+                levels.remove(&price);
+            }
+        }
+    }
+
+    fn find_mut(&mut self, order_id: &OrderId) -> Option<&mut BookOrder> {
+        let (side, price) = *self.orders.get(order_id)?;
+        self.levels(side).get_mut(&price)?.iter_mut().find(|order| order.order_id == *order_id)
+    }
+}
+
+/// Rebuilds the book as it was at 'time' from a snapshot and the messages recorded after it.
+pub fn book_at<'a, I>(snapshot: &OrderBookL3, messages: I, time: DateTime<Utc>) -> Result<OrderBookL3, anyhow::Error>
+where
+    I: IntoIterator<Item = &'a FullMessage>,
+{
+    let mut replay = L3Replay::new(snapshot);
+    replay.replay_until(messages, time)?;
+    Ok(replay.book())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGES: &str = r#"
+{"type": "subscriptions", "channels": []}
+{"type": "received", "sequence": 10, "time": "2022-10-10T10:00:01Z", "order_id": "00000000-0000-0000-0000-000000000001", "side": "buy", "order_type": "limit", "price": "99", "size": "1"}
+{"type": "received", "sequence": 11, "time": "2022-10-10T10:00:01Z", "order_id": "00000000-0000-0000-0000-00000000000c", "side": "buy", "order_type": "limit", "price": "99", "size": "2"}
+{"type": "open", "sequence": 12, "time": "2022-10-10T10:00:01Z", "order_id": "00000000-0000-0000-0000-00000000000c", "side": "buy", "price": "99", "remaining_size": "2"}
+{"type": "match", "sequence": 13, "time": "2022-10-10T10:00:02Z", "trade_id": 7, "maker_order_id": "00000000-0000-0000-0000-00000000000b", "taker_order_id": "00000000-0000-0000-0000-000000000002", "side": "sell", "price": "101", "size": "1.5"}
+{"type": "change", "sequence": 14, "time": "2022-10-10T10:00:03Z", "order_id": "00000000-0000-0000-0000-00000000000a", "side": "buy", "new_size": "0.5", "old_size": "1"}
+{"type": "done", "sequence": 15, "time": "2022-10-10T10:00:04Z", "order_id": "00000000-0000-0000-0000-00000000000b", "side": "sell", "reason": "canceled", "price": "101", "remaining_size": "0.5"}
+"#;
+
+    fn snapshot() -> OrderBookL3 {
+        serde_json::from_str(r#"{"sequence": 10, "bids": [["99", "1", "00000000-0000-0000-0000-00000000000a"]],
+            "asks": [["101", "2", "00000000-0000-0000-0000-00000000000b"]]}"#).unwrap()
+    }
+
+    fn time(seconds: u32) -> DateTime<Utc> {
+        format!("2022-10-10T10:00:0{}Z", seconds).parse().unwrap()
+    }
+
+    #[test]
+    fn test_replay() {
+        let messages: Vec<FullMessage> = read_messages(MESSAGES.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages[0], FullMessage::Other);
+
+        let at = |seconds| book_at(&snapshot(), &messages, time(seconds)).unwrap();
+        assert_eq!(at(0), OrderBookL3 { extra: HashMap::new(), ..snapshot() });
+
+        let book = at(2);
+        assert_eq!(book.sequence, 13);
+        let ids: Vec<String> = book.bids.iter().map(|order| order.order_id.to_string()).collect();
+        assert_eq!(ids, ["00000000-0000-0000-0000-00000000000a", "00000000-0000-0000-0000-00000000000c"]);
+        assert_eq!(book.asks[0].size, Decimal::new(5, 1));
+
+        let book = at(4);
+        assert_eq!(book.bids[0].size, Decimal::new(5, 1));
+        assert!(book.asks.is_empty());
+    }
+
+    #[test]
+    fn test_sequence_gap() {
+        let messages: Vec<FullMessage> = read_messages(MESSAGES.as_bytes()).collect::<Result<_, _>>().unwrap();
+        let mut replay = L3Replay::new(&snapshot());
+        for message in &messages[..4] {
+            replay.apply(message).unwrap();
+        }
+
+        let err = replay.apply(&messages[5]).unwrap_err();
+        assert_eq!(err.downcast_ref::<SequenceGap>(), Some(&SequenceGap { expected: 13, received: 14 }));
+        assert_eq!(replay.sequence(), 12);
+    }
+}
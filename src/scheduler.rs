@@ -0,0 +1,151 @@
+//! Priority ordering of requests waiting for the rate limiter.
+//!
+//! Requests are sent with Priority::LiveData unless they run inside with_priority. When the
+//! rate limiter is saturated, the next permit always goes to the highest priority request
+//! waiting, and requests of the same priority go first come, first served, so a long
+//! backfill can't starve time-sensitive calls sharing the same client:
+//!
+//!             use coinbase_pro_api::{CoinbasePublicClient, Granularity};
+//!             use coinbase_pro_api::scheduler::{with_priority, Priority};
+//!
+//!             async fn backfill(client: &CoinbasePublicClient) -> Result<String, anyhow::Error> {
+//!                 with_priority(Priority::Backfill, async {
+//!                     client.get_product_historic_rates("ETH-USD", None, None, Some(Granularity::Minute1)).await
+//!                 }).await
+//!             }
+
+// std
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::Mutex;
+// external
+use governor::{
+    RateLimiter,
+    clock::{Clock, DefaultClock},
+    state::{InMemoryState, NotKeyed}
+};
+use tokio::sync::Notify;
+
+tokio::task_local! {
+    static PRIORITY: Priority;
+}
+
+/// Priority of a request, highest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Calls that trading decisions wait on, such as a book check before placing an order.
+    Trading,
+    /// Ongoing market data polling. The default.
+    #[default]
+    LiveData,
+    /// Bulk historic downloads that can wait.
+    Backfill,
+}
+
+/// Runs 'future' with every client request it sends tagged with 'priority'. Tasks spawned
+/// from inside it don't inherit the priority.
+pub async fn with_priority<F: Future>(priority: Priority, future: F) -> F::Output {
+    PRIORITY.scope(priority, future).await
+}
+
+/// Priority of the current request, set by with_priority.
+fn current_priority() -> Priority {
+    PRIORITY.try_with(|priority| *priority).unwrap_or_default()
+}
+
+/// Queue of requests waiting for the rate limiter, ordered by priority, then arrival.
+#[derive(Debug, Default)]
+pub(crate) struct Scheduler {
+    waiting: Mutex<Waiting>,
+    changed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct Waiting {
+    next_ticket: u64,
+    queue: BTreeSet<(Priority, u64)>,
+}
+
+impl Scheduler {
+    /// Waits until the caller is first in line and the rate limiter allows a request. A
+    /// request that arrives while the first in line is waiting on the limiter takes its place
+    /// if it has a higher priority.
+    pub(crate) async fn acquire(&self, rate_limiter: &RateLimiter<NotKeyed, InMemoryState, DefaultClock>) {
+        let key = {
+            let mut waiting = self.waiting.lock().unwrap();
+            let key = (current_priority(), waiting.next_ticket);
+            waiting.next_ticket += 1;
+            waiting.queue.insert(key);
+            key
+        };
+        let _ticket = Ticket { scheduler: self, key };
+        self.changed.notify_waiters();
+
+        loop {
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.waiting.lock().unwrap().queue.first() != Some(&key) {
+                notified.await;
+                continue;
+            }
+            match rate_limiter.check() {
+                Ok(()) => return,
+                Err(not_until) => {
+                    let wait = not_until.wait_time_from(DefaultClock::default().now());
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {},
+                        _ = notified => {},
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Removes a request from the queue once it has its permit or is cancelled, and wakes the
+/// others to see who is next.
+struct Ticket<'a> {
+    scheduler: &'a Scheduler,
+    key: (Priority, u64),
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        self.scheduler.waiting.lock().unwrap().queue.remove(&self.key);
+        self.scheduler.changed.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use governor::Quota;
+
+    #[tokio::test]
+    async fn test_higher_priority_goes_first() {
+        let rate_limiter = RateLimiter::direct(Quota::with_period(Duration::from_millis(50)).unwrap());
+        let scheduler = Scheduler::default();
+        let order = Mutex::new(Vec::new());
+
+        let request = |priority: Priority, delay: u64| {
+            let (scheduler, rate_limiter, order) = (&scheduler, &rate_limiter, &order);
+            async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                with_priority(priority, scheduler.acquire(rate_limiter)).await;
+                order.lock().unwrap().push(priority);
+            }
+        };
+        // the first backfill takes the only permit, and the others queue up behind it
+        tokio::join!(
+            request(Priority::Backfill, 0),
+            request(Priority::Backfill, 5),
+            request(Priority::LiveData, 10),
+            request(Priority::Trading, 15),
+        );
+        assert_eq!(*order.lock().unwrap(), [Priority::Backfill, Priority::Trading, Priority::LiveData, Priority::Backfill]);
+        assert!(scheduler.waiting.lock().unwrap().queue.is_empty());
+    }
+}
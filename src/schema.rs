@@ -0,0 +1,131 @@
+//! Detection of response fields the typed models don't know about, so API changes can be
+//! caught early.
+//!
+//! Models for json objects keep fields they don't cover in their 'extra' map, so nothing
+//! Coinbase adds is lost in lenient mode. The client can also count unknown fields across
+//! responses, see CoinbaseClientBuilder::track_unknown_fields.
+
+// std
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+// external
+use serde::Serialize;
+use serde_json::Value;
+
+/// How typed responses treat json the models don't cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DeserializeMode {
+    /// Ignore unknown fields and map unknown enum values to their catch-all variant, such as
+    /// ProductStatus::Unknown. Suited to production, where Coinbase adding a field must not
+    /// break anything.
+    #[default]
+    Lenient,
+    /// Fail with an UnknownFields error whenever a response has a field or enum value the
+    /// models don't cover. Suited to CI, to catch API drift early.
+    Strict,
+}
+
+/// Error returned in strict mode for responses with fields or enum values the models don't
+/// cover. Retrieve it with anyhow's downcast_ref.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownFields {
+    pub endpoint: String,
+    /// Paths of the unknown fields, such as '[0].auction_mode' or '.bids[0][3]'.
+    pub paths: Vec<String>,
+}
+
+impl fmt::Display for UnknownFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "response from {} has unknown fields: {}", self.endpoint, self.paths.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownFields {}
+
+/// Value that catch-all enum variants serialize to.
+const UNKNOWN_VARIANT: &str = "unknown";
+
+thread_local! {
+    static OMIT_EXTRA: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether a model's 'extra' map is left out when serializing: always when it is empty, and
+/// for every model while serializing through to_value_without_extra.
+pub(crate) fn skip_extra(extra: &HashMap<String, Value>) -> bool {
+    extra.is_empty() || OMIT_EXTRA.with(Cell::get)
+}
+
+/// Serializes 'model' without the unknown fields captured in its 'extra' maps, which is what
+/// unknown_fields needs to compare against.
+pub fn to_value_without_extra<T: Serialize>(model: &T) -> serde_json::Result<Value> {
+    OMIT_EXTRA.with(|omit| {
+        let previous = omit.replace(true);
+        let value = serde_json::to_value(model);
+        omit.set(previous);
+        value
+    })
+}
+
+/// Compares a response with its parsed model serialized by to_value_without_extra, and
+/// returns the paths present in 'original' that the model doesn't cover: object keys, extra
+/// array elements, and enum values that parsed into a catch-all variant.
+pub fn unknown_fields(original: &Value, parsed: &Value) -> Vec<String> {
+    let mut paths = Vec::new();
+    collect_unknown(original, parsed, String::new(), &mut paths);
+    paths
+}
+
+fn collect_unknown(original: &Value, parsed: &Value, path: String, paths: &mut Vec<String>) {
+    match (original, parsed) {
+        (Value::Object(original), Value::Object(parsed)) => {
+            for (key, value) in original {
+                let field_path = format!("{}.{}", path, key);
+                match parsed.get(key) {
+                    Some(parsed_value) => collect_unknown(value, parsed_value, field_path, paths),
+                    None => paths.push(field_path),
+                }
+            }
+        },
+        (Value::Array(original), Value::Array(parsed)) => {
+            for (i, value) in original.iter().enumerate() {
+                let element_path = format!("{}[{}]", path, i);
+                match parsed.get(i) {
+                    Some(parsed_value) => collect_unknown(value, parsed_value, element_path, paths),
+                    None => paths.push(element_path),
+                }
+            }
+        },
+        (Value::String(original), Value::String(parsed)) if parsed == UNKNOWN_VARIANT && original != UNKNOWN_VARIANT => {
+            paths.push(path);
+        },
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Product;
+
+    fn check<T: serde::de::DeserializeOwned + serde::Serialize>(json: &str) -> Vec<String> {
+        let parsed: T = serde_json::from_str(json).unwrap();
+        unknown_fields(&serde_json::from_str(json).unwrap(), &to_value_without_extra(&parsed).unwrap())
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let product = r#"{"id": "ETH-USD", "base_currency": "ETH", "quote_currency": "USD",
+            "quote_increment": "0.01", "base_increment": "0.00000001", "display_name": "ETH/USD",
+            "status": "paused", "auction_mode": false}"#;
+        assert_eq!(check::<Product>(product), [".auction_mode", ".status"]);
+        assert_eq!(check::<Vec<Product>>(&format!("[{}]", product)), ["[0].auction_mode", "[0].status"]);
+
+        let parsed: Product = serde_json::from_str(product).unwrap();
+        assert_eq!(parsed.extra["auction_mode"], Value::Bool(false));
+        assert_eq!(serde_json::to_value(&parsed).unwrap()["auction_mode"], Value::Bool(false));
+
+        let known = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/products_eth-usd_ticker.json")).unwrap();
+        assert!(check::<crate::models::Ticker>(&known).is_empty());
+    }
+}
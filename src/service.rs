@@ -0,0 +1,98 @@
+//! The client's request layer as a tower::Service, so standard tower layers such as timeouts,
+//! load shedding or concurrency limits can wrap it:
+//!
+//!             use std::sync::Arc;
+//!             use coinbase_pro_api::CoinbasePublicClient;
+//!             use coinbase_pro_api::service::{CoinbaseRequest, CoinbaseService};
+//!
+//!             let service = CoinbaseService::new(Arc::new(CoinbasePublicClient::new()));
+//!             let request = CoinbaseRequest::new("/products/ETH-USD/book").param("level", "2");
+//!
+//! Requests still go through the client's rate limiter. To rate limit with a tower layer
+//! instead, build the client with rate_limit(0).
+
+// std
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+// external
+use tower_service::Service;
+// crate
+use crate::CoinbasePublicClient;
+
+/// GET request to a public endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CoinbaseRequest {
+    /// Path such as '/products/ETH-USD/book'.
+    pub endpoint: String,
+    /// Query string parameters, in order.
+    pub params: Vec<(String, String)>,
+}
+
+impl CoinbaseRequest {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self { endpoint: endpoint.into(), params: Vec::new() }
+    }
+
+    /// Adds a query string parameter.
+    pub fn param(mut self, key: impl Into<String>, value: impl ToString) -> Self {
+        self.params.push((key.into(), value.to_string()));
+        self
+    }
+}
+
+/// Service answering CoinbaseRequests with the json response body. Cloning it shares the
+/// client.
+#[derive(Debug, Clone)]
+pub struct CoinbaseService {
+    client: Arc<CoinbasePublicClient>,
+}
+
+impl CoinbaseService {
+    pub fn new(client: Arc<CoinbasePublicClient>) -> Self {
+        Self { client }
+    }
+
+    pub fn client(&self) -> &Arc<CoinbasePublicClient> {
+        &self.client
+    }
+}
+
+impl Service<CoinbaseRequest> for CoinbaseService {
+    type Response = String;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<String, anyhow::Error>> + Send>>;
+
+    /// Always ready, since requests queue for the rate limiter once called.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: CoinbaseRequest) -> Self::Future {
+        let client = self.client.clone();
+        Box::pin(async move {
+            let params = (!request.params.is_empty()).then_some(request.params);
+            client.get_json(&request.endpoint, params).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::CannedResponses;
+
+    #[tokio::test]
+    async fn test_service_call() {
+        let client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new().with("/products/ETH-USD/book?level=2", r#"{"level": 2}"#))
+            .build();
+        let mut service = CoinbaseService::new(Arc::new(client));
+
+        std::future::poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let book = service.call(CoinbaseRequest::new("/products/ETH-USD/book").param("level", 2)).await.unwrap();
+        assert_eq!(book, r#"{"level": 2}"#);
+        assert!(service.call(CoinbaseRequest::new("/products")).await.is_err());
+    }
+}
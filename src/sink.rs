@@ -0,0 +1,193 @@
+//! Storage for collected market data, behind a trait so downstream crates can add sinks for
+//! databases or object stores.
+
+// std
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+// external
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+// crate
+use crate::{CoinbasePublicClient, Granularity};
+use crate::candles;
+use crate::models::{Candle, Trade};
+use crate::product_id::ProductId;
+use crate::replay::FullMessage;
+
+/// Destination for batches of market data.
+pub trait DataSink {
+    fn write_trades(&mut self, product_id: &ProductId, trades: &[Trade]) -> impl Future<Output = Result<(), anyhow::Error>> + Send;
+
+    fn write_candles(
+        &mut self,
+        product_id: &ProductId,
+        granularity: Granularity,
+        candles: &[Candle]
+    ) -> impl Future<Output = Result<(), anyhow::Error>> + Send;
+
+    fn write_book_events(&mut self, product_id: &ProductId, messages: &[FullMessage]) -> impl Future<Output = Result<(), anyhow::Error>> + Send;
+
+    /// Makes everything written so far durable.
+    fn flush(&mut self) -> impl Future<Output = Result<(), anyhow::Error>> + Send;
+
+    /// Flushes and starts new files, tables or objects for subsequent writes, such as once a
+    /// day. Sinks without such a notion can just flush.
+    fn rotate(&mut self) -> impl Future<Output = Result<(), anyhow::Error>> + Send;
+}
+
+/// Writes each kind of data for each product to its own json lines file, one record per line,
+/// in the same format the models deserialize from. Full channel messages can be read back with
+/// replay::read_messages.
+///
+/// Files are named like 'eth-usd_trades.0.jsonl' or 'eth-usd_candles_60.0.jsonl', where the
+/// number is the segment, which rotate increments. Existing files are appended to.
+///
+/// Records are buffered until flush, which also syncs the files to disk. Dropping the sink
+/// without flushing loses buffered records, since tokio's BufWriter doesn't flush on drop.
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    dir: PathBuf,
+    segment: u32,
+    files: HashMap<String, BufWriter<File>>,
+}
+
+impl JsonLinesSink {
+    /// Writes into 'dir', which is created on the first write if needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self::with_segment(dir, 0)
+    }
+
+    /// Like new, starting at 'segment', such as to carry on after a restart.
+    pub fn with_segment(dir: impl Into<PathBuf>, segment: u32) -> Self {
+        Self { dir: dir.into(), segment, files: HashMap::new() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn segment(&self) -> u32 {
+        self.segment
+    }
+
+    async fn write_lines<T: Serialize>(&mut self, name: String, records: &[T]) -> Result<(), anyhow::Error> {
+        let mut lines = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut lines, record).context("failed to serialize record")?;
+            lines.push(b'\n');
+        }
+
+        let file = match self.files.get_mut(&name) {
+            Some(file) => file,
+            None => {
+                let path = self.dir.join(format!("{}.{}.jsonl", name, self.segment));
+                fs::create_dir_all(&self.dir).await
+                    .with_context(|| format!("failed to create directory {}", self.dir.display()))?;
+                let file = OpenOptions::new().create(true).append(true).open(&path).await
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                self.files.entry(name).or_insert(BufWriter::new(file))
+            },
+        };
+        file.write_all(&lines).await.context("failed to write records")
+    }
+}
+
+/// File name prefix for a product, lowercased like fixture names.
+fn file_prefix(product_id: &ProductId, kind: &str) -> String {
+    format!("{}_{}", product_id.as_str().to_ascii_lowercase(), kind)
+}
+
+impl DataSink for JsonLinesSink {
+    async fn write_trades(&mut self, product_id: &ProductId, trades: &[Trade]) -> Result<(), anyhow::Error> {
+        self.write_lines(file_prefix(product_id, "trades"), trades).await
+    }
+
+    async fn write_candles(
+        &mut self,
+        product_id: &ProductId,
+        granularity: Granularity,
+        candles: &[Candle]
+    ) -> Result<(), anyhow::Error> {
+        let kind = format!("candles_{}", granularity.seconds());
+        self.write_lines(file_prefix(product_id, &kind), candles).await
+    }
+
+    async fn write_book_events(&mut self, product_id: &ProductId, messages: &[FullMessage]) -> Result<(), anyhow::Error> {
+        self.write_lines(file_prefix(product_id, "book_events"), messages).await
+    }
+
+    async fn flush(&mut self) -> Result<(), anyhow::Error> {
+        for file in self.files.values_mut() {
+            file.flush().await.context("failed to flush records")?;
+            file.get_mut().sync_data().await.context("failed to sync records to disk")?;
+        }
+        Ok(())
+    }
+
+    async fn rotate(&mut self) -> Result<(), anyhow::Error> {
+        self.flush().await?;
+        self.files.clear();
+        self.segment += 1;
+        Ok(())
+    }
+}
+
+/// Downloads candles between 'start' and 'end' (inclusive) into 'sink', one request at a
+/// time, so long ranges never have to fit in memory. Returns the number of candles written.
+/// The sink is flushed at the end.
+pub async fn backfill_candles<S: DataSink>(
+    client: &CoinbasePublicClient,
+    sink: &mut S,
+    product_id: &ProductId,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    granularity: Granularity
+) -> Result<usize, anyhow::Error> {
+    let mut written = 0;
+    for (chunk_start, chunk_end) in candles::request_chunks(start, end, granularity) {
+        let candles = client.get_candles_chunked(product_id, chunk_start, chunk_end, granularity).await?;
+        sink.write_candles(product_id, granularity, &candles).await?;
+        written += candles.len();
+    }
+    sink.flush().await?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use crate::testing::CannedResponses;
+
+    #[tokio::test]
+    async fn test_backfill_into_json_lines() {
+        let dir = std::env::temp_dir().join(format!("coinbase_pro_api_sink_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let product_id: ProductId = "ETH-USD".parse().unwrap();
+
+        // one candle in each of the two requests
+        let client = CoinbasePublicClient::builder()
+            .offline(CannedResponses::new().with("/products/ETH-USD/candles", "[[1665417000, 1, 2, 1, 2, 5], [1665396000, 1, 2, 1, 2, 5]]"))
+            .build();
+        let start = Utc.timestamp_opt(1665396000, 0).unwrap();
+        let mut sink = JsonLinesSink::new(&dir);
+        let written = backfill_candles(&client, &mut sink, &product_id, start, start + Duration::minutes(400), Granularity::Minute1).await.unwrap();
+        assert_eq!(written, 2);
+
+        let saved = std::fs::read_to_string(dir.join("eth-usd_candles_60.0.jsonl")).unwrap();
+        let candles: Vec<Candle> = saved.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(candles.iter().map(|candle| candle.time.timestamp()).collect::<Vec<_>>(), [1665396000, 1665417000]);
+
+        sink.rotate().await.unwrap();
+        let trades: Vec<Trade> = serde_json::from_str(include_str!("../tests/fixtures/products_eth-usd_trades.json")).unwrap();
+        sink.write_trades(&product_id, &trades).await.unwrap();
+        sink.flush().await.unwrap();
+        let saved = std::fs::read_to_string(dir.join("eth-usd_trades.1.jsonl")).unwrap();
+        assert_eq!(saved.lines().count(), trades.len());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
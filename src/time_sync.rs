@@ -0,0 +1,82 @@
+//! Estimation of the offset between the local clock and Coinbase's server clock.
+
+// external
+use chrono::{DateTime, Duration, Utc};
+
+/// One round trip to the time endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSample {
+    /// Local time just before the request was sent.
+    pub sent_at: DateTime<Utc>,
+    /// Local time just after the response arrived.
+    pub received_at: DateTime<Utc>,
+    /// Time reported by the server.
+    pub server_time: DateTime<Utc>,
+}
+
+impl TimeSample {
+    pub fn rtt(&self) -> Duration {
+        self.received_at - self.sent_at
+    }
+
+    /// Server time minus local time, assuming the server stamped its response halfway
+    /// through the round trip.
+    pub fn offset(&self) -> Duration {
+        self.server_time - (self.sent_at + self.rtt() / 2)
+    }
+}
+
+/// Estimated skew between the local clock and the server clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSkew {
+    /// Server time minus local time. Positive when the local clock is behind.
+    pub offset: Duration,
+    /// Round trip time of the sample the offset was taken from.
+    pub rtt: Duration,
+    /// Local time at which the estimate was made.
+    pub synced_at: DateTime<Utc>,
+}
+
+impl ClockSkew {
+    /// Picks the sample with the lowest round trip time, since it bounds the offset most tightly.
+    pub fn estimate(samples: &[TimeSample]) -> Option<Self> {
+        samples.iter()
+            .min_by_key(|sample| sample.rtt())
+            .map(|sample| ClockSkew {
+                offset: sample.offset(),
+                rtt: sample.rtt(),
+                synced_at: sample.received_at,
+            })
+    }
+
+    /// Converts a local time into estimated server time.
+    pub fn to_server_time(&self, local: DateTime<Utc>) -> DateTime<Utc> {
+        local + self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(1665396000000 + millis).unwrap()
+    }
+
+    #[test]
+    fn test_estimate_uses_fastest_sample() {
+        let samples = [
+            TimeSample { sent_at: at(0), received_at: at(300), server_time: at(1400) },
+            TimeSample { sent_at: at(1000), received_at: at(1100), server_time: at(2550) },
+            TimeSample { sent_at: at(2000), received_at: at(2200), server_time: at(3350) },
+        ];
+        let skew = ClockSkew::estimate(&samples).unwrap();
+        assert_eq!(skew.rtt, Duration::milliseconds(100));
+        assert_eq!(skew.offset, Duration::milliseconds(1500));
+        assert_eq!(skew.synced_at, at(1100));
+        assert_eq!(skew.to_server_time(at(5000)), at(6500));
+
+        assert!(ClockSkew::estimate(&[]).is_none());
+    }
+}
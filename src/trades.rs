@@ -0,0 +1,112 @@
+//! Utilities for merging and deduplicating trade prints.
+
+// crate
+use crate::models::Trade;
+
+/// Merges trades from any number of sources into a single list ordered by trade_id,
+/// with each trade appearing exactly once. When duplicates are found, the first occurrence wins.
+///
+/// # Arguments
+///
+/// * 'trades' - trades in any order, such as several pages of 'get_product_trades' chained together.
+pub fn dedup_trades<I>(trades: I) -> Vec<Trade>
+where
+    I: IntoIterator<Item = Trade>,
+{
+    let mut trades: Vec<Trade> = trades.into_iter().collect();
+    trades.sort_by_key(|trade| trade.trade_id);
+    trades.dedup_by_key(|trade| trade.trade_id);
+    trades
+}
+
+/// Stateful deduplicator for stitching a live trade feed onto historic data.
+///
+/// Remembers the highest trade_id emitted so far and drops anything at or below it,
+/// guaranteeing output is strictly increasing by trade_id.
+#[derive(Debug, Clone, Default)]
+pub struct TradeDeduplicator {
+    last_trade_id: Option<u64>,
+}
+
+impl TradeDeduplicator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resume deduplication after a trade that has already been processed.
+    pub fn resume_after(last_trade_id: u64) -> Self {
+        Self { last_trade_id: Some(last_trade_id) }
+    }
+
+    /// Highest trade_id emitted so far.
+    pub fn last_trade_id(&self) -> Option<u64> {
+        self.last_trade_id
+    }
+
+    /// Returns the trade if it has not been seen yet, or None if it is a duplicate or older
+    /// than a trade that was already emitted.
+    pub fn push(&mut self, trade: Trade) -> Option<Trade> {
+        match self.last_trade_id {
+            Some(last) if trade.trade_id <= last => None,
+            _ => {
+                self.last_trade_id = Some(trade.trade_id);
+                Some(trade)
+            }
+        }
+    }
+
+    /// Sorts a batch of trades (such as a REST page, which Coinbase returns newest first)
+    /// and returns only the trades that have not been seen yet, in order.
+    pub fn extend<I>(&mut self, trades: I) -> Vec<Trade>
+    where
+        I: IntoIterator<Item = Trade>,
+    {
+        dedup_trades(trades)
+            .into_iter()
+            .filter_map(|trade| self.push(trade))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trades_from_json(json: &str) -> Vec<Trade> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn trade_ids(trades: &[Trade]) -> Vec<u64> {
+        trades.iter().map(|trade| trade.trade_id).collect()
+    }
+
+    const PAGE_1: &str = r#"[
+        {"time":"2022-10-10T10:00:03.000Z","trade_id":103,"price":"1300.10","size":"0.5","side":"buy"},
+        {"time":"2022-10-10T10:00:02.000Z","trade_id":102,"price":"1300.05","size":"1.0","side":"sell"},
+        {"time":"2022-10-10T10:00:01.000Z","trade_id":101,"price":"1300.00","size":"0.2","side":"buy"}
+    ]"#;
+
+    const PAGE_2: &str = r#"[
+        {"time":"2022-10-10T10:00:04.000Z","trade_id":104,"price":"1300.20","size":"0.1","side":"sell"},
+        {"time":"2022-10-10T10:00:03.000Z","trade_id":103,"price":"1300.10","size":"0.5","side":"buy"}
+    ]"#;
+
+    #[test]
+    fn test_dedup_trades() {
+        let merged = dedup_trades(
+            trades_from_json(PAGE_1).into_iter().chain(trades_from_json(PAGE_2))
+        );
+        assert_eq!(trade_ids(&merged), vec![101, 102, 103, 104]);
+    }
+
+    #[test]
+    fn test_deduplicator() {
+        let mut dedup = TradeDeduplicator::new();
+        assert_eq!(trade_ids(&dedup.extend(trades_from_json(PAGE_1))), vec![101, 102, 103]);
+        assert_eq!(trade_ids(&dedup.extend(trades_from_json(PAGE_2))), vec![104]);
+        assert_eq!(dedup.last_trade_id(), Some(104));
+
+        let stale = trades_from_json(PAGE_1).remove(0);
+        assert!(dedup.push(stale).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+//! Pluggable http transport, for sending requests through isahc, surf or any other stack
+//! without depending on reqwest or hyper.
+//!
+//! Implement HttpTransport and pass it to the builder's transport method. Requests and
+//! responses are the http crate's types, so adapters can live outside this crate:
+//!
+//!             use coinbase_pro_api::CoinbasePublicClient;
+//!             use coinbase_pro_api::transport::{HttpTransport, TransportFuture};
+//!
+//!             struct Canned;
+//!
+//!             impl HttpTransport for Canned {
+//!                 fn send(&self, _request: http::Request<()>) -> TransportFuture<'_> {
+//!                     Box::pin(async { Ok(http::Response::new(b"{}".to_vec())) })
+//!                 }
+//!             }
+//!
+//!             let client = CoinbasePublicClient::builder().transport(Canned).build();
+//!
+//! Without the reqwest and hyper features, a transport must be set, or every request fails.
+
+// std
+use std::future::Future;
+use std::pin::Pin;
+
+/// Future returned by HttpTransport::send.
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<http::Response<Vec<u8>>, anyhow::Error>> + Send + 'a>>;
+
+/// Sends GET requests over the network and returns the whole response.
+pub trait HttpTransport: Send + Sync {
+    /// Sends 'request' as given, including its User-Agent header, since Coinbase rejects
+    /// requests without one. Responses with error statuses should be returned rather than
+    /// turned into errors; the client checks the status itself. Errors are for requests that
+    /// got no response. The client applies its request timeout around this.
+    fn send(&self, request: http::Request<()>) -> TransportFuture<'_>;
+}
+
+/// Transport used when no http client feature is enabled and none was set.
+#[cfg(not(any(feature = "reqwest", feature = "hyper")))]
+pub(crate) struct NoTransport;
+
+#[cfg(not(any(feature = "reqwest", feature = "hyper")))]
+impl HttpTransport for NoTransport {
+    fn send(&self, _request: http::Request<()>) -> TransportFuture<'_> {
+        Box::pin(async { Err(anyhow::anyhow!("no http transport: enable the reqwest or hyper feature or set one on the builder")) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use anyhow::anyhow;
+    use crate::CoinbasePublicClient;
+    use crate::health::ServerError;
+
+    /// Answers from a map of paths and records each request.
+    struct Recording {
+        requests: Arc<Mutex<Vec<http::Request<()>>>>,
+    }
+
+    impl HttpTransport for Recording {
+        fn send(&self, request: http::Request<()>) -> TransportFuture<'_> {
+            let response = match request.uri().path() {
+                "/time" => Ok(http::Response::new(br#"{"iso":"2022-10-10T10:00:00Z","epoch":1665396000.0}"#.to_vec())),
+                "/products" => Ok(http::Response::builder().status(503).body(b"unavailable".to_vec()).unwrap()),
+                path => Err(anyhow!("no route to {}", path)),
+            };
+            self.requests.lock().unwrap().push(request);
+            Box::pin(async { response })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_transport() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let client = CoinbasePublicClient::builder()
+            .transport(Recording { requests: requests.clone() })
+            .build();
+
+        let time: serde_json::Value = serde_json::from_str(&client.get_time().await.unwrap()).unwrap();
+        assert_eq!(time["epoch"], 1665396000.0);
+        let err = client.get_products().await.unwrap_err();
+        assert_eq!(err.downcast_ref::<ServerError>(), Some(&ServerError { status: 503 }));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].method(), http::Method::GET);
+        assert_eq!(requests[0].uri(), "https://api.pro.coinbase.com/time");
+        assert_eq!(requests[0].headers()[http::header::USER_AGENT], crate::APP_USER_AGENT);
+    }
+}
@@ -0,0 +1,107 @@
+//! Typed accessors for json responses, for callers using the string-returning methods without
+//! the typed models:
+//!
+//!             use coinbase_pro_api::value::ValueExt;
+//!
+//!             let ticker: serde_json::Value = serde_json::from_str(
+//!                 r#"{"price": "1300.51", "time": "2022-10-10T10:00:00Z", "bids": [["1300.5", "2"]]}"#
+//!             ).unwrap();
+//!             assert_eq!(ticker.decimal("price").unwrap().to_string(), "1300.51");
+//!             assert_eq!(ticker.decimal("bids.0.1").unwrap().to_string(), "2");
+//!             assert!(ticker.decimal("ask").unwrap_err().to_string().contains("'ask'"));
+
+// std
+use std::str::FromStr;
+// external
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+/// Accessors taking a dotted path of object keys and array indices, such as 'price' or
+/// 'bids.0.1'. Errors name the path and what was found there.
+pub trait ValueExt {
+    /// Value at 'path'.
+    fn at(&self, path: &str) -> Result<&Value, anyhow::Error>;
+
+    /// Decimal at 'path', from a string such as "1300.51" or a plain number.
+    fn decimal(&self, path: &str) -> Result<Decimal, anyhow::Error>;
+
+    /// Time at 'path', from an RFC3339 string or unix timestamp in seconds.
+    fn datetime(&self, path: &str) -> Result<DateTime<Utc>, anyhow::Error>;
+
+    /// Unsigned integer at 'path', such as a trade id or sequence number.
+    fn u64(&self, path: &str) -> Result<u64, anyhow::Error>;
+
+    /// String at 'path'.
+    fn str(&self, path: &str) -> Result<&str, anyhow::Error>;
+}
+
+impl ValueExt for Value {
+    fn at(&self, path: &str) -> Result<&Value, anyhow::Error> {
+        let pointer: String = path.split('.').map(|segment| format!("/{}", segment)).collect();
+        self.pointer(&pointer).ok_or_else(|| anyhow!("missing '{}'", path))
+    }
+
+    fn decimal(&self, path: &str) -> Result<Decimal, anyhow::Error> {
+        match self.at(path)? {
+            Value::String(text) => parse_decimal(text),
+            Value::Number(number) => parse_decimal(&number.to_string()),
+            other => Err(anyhow!("found {}", other)),
+        }.with_context(|| format!("'{}' is not a decimal", path))
+    }
+
+    fn datetime(&self, path: &str) -> Result<DateTime<Utc>, anyhow::Error> {
+        match self.at(path)? {
+            Value::String(text) => DateTime::parse_from_rfc3339(text)
+                .map(|time| time.with_timezone(&Utc))
+                .map_err(anyhow::Error::from),
+            Value::Number(number) => number.as_f64()
+                .and_then(|seconds| Utc.timestamp_millis_opt((seconds * 1000.0).round() as i64).single())
+                .ok_or_else(|| anyhow!("timestamp {} is out of range", number)),
+            other => Err(anyhow!("found {}", other)),
+        }.with_context(|| format!("'{}' is not a time", path))
+    }
+
+    fn u64(&self, path: &str) -> Result<u64, anyhow::Error> {
+        let value = self.at(path)?;
+        value.as_u64()
+            .or_else(|| value.as_str().and_then(|text| text.parse().ok()))
+            .ok_or_else(|| anyhow!("'{}' is not an unsigned integer, found {}", path, value))
+    }
+
+    fn str(&self, path: &str) -> Result<&str, anyhow::Error> {
+        let value = self.at(path)?;
+        value.as_str().ok_or_else(|| anyhow!("'{}' is not a string, found {}", path, value))
+    }
+}
+
+/// Parses plain and scientific notation, which Coinbase uses for some tiny sizes.
+fn parse_decimal(text: &str) -> Result<Decimal, anyhow::Error> {
+    Decimal::from_str(text)
+        .or_else(|_| Decimal::from_scientific(text))
+        .map_err(|_| anyhow!("found '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accessors() {
+        let time: Value = serde_json::from_str(include_str!("../tests/fixtures/time.json")).unwrap();
+        assert_eq!(time.datetime("iso").unwrap(), time.datetime("epoch").unwrap());
+
+        let book: Value = serde_json::from_str(include_str!("../tests/fixtures/products_eth-usd_book__level=2.json")).unwrap();
+        assert!(book.u64("sequence").is_ok());
+        assert!(book.decimal("bids.0.0").unwrap() > book.decimal("bids.1.0").unwrap());
+        assert_eq!(book.decimal("bids.0.2").unwrap(), Decimal::from(book.u64("bids.0.2").unwrap()));
+
+        let err = book.decimal("bids").unwrap_err();
+        assert!(format!("{:#}", err).starts_with("'bids' is not a decimal: found ["));
+        assert_eq!(book.str("asks.999.0").unwrap_err().to_string(), "missing 'asks.999.0'");
+
+        let tiny: Value = serde_json::json!({"size": "1e-8"});
+        assert_eq!(tiny.decimal("size").unwrap(), Decimal::new(1, 8));
+    }
+}
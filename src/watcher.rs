@@ -0,0 +1,113 @@
+//! Background polling of any endpoint that only reports changes, such as a product's 24h stats
+//! or status.
+
+// std
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+// external
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::broadcast;
+use tokio::time::MissedTickBehavior;
+// crate
+use crate::CoinbasePublicClient;
+use crate::background::{Background, CHANNEL_CAPACITY};
+
+/// Fetches an endpoint at a fixed interval, decodes it into T and emits the value only when it
+/// differs from the previous one, including the first value fetched.
+///
+/// A failed request or decode is skipped and the next value is compared against the last good
+/// one. Polls missed while a request waits on the rate limiter are skipped rather than sent in
+/// a burst. Polling runs on the current tokio runtime until the watcher is dropped.
+#[derive(Debug)]
+pub struct Watcher<T> {
+    latest: Arc<RwLock<Option<T>>>,
+    changes: Background<T>,
+}
+
+impl<T> Watcher<T>
+where
+    T: DeserializeOwned + Serialize + Clone + Send + Sync + 'static,
+{
+    /// Starts polling, comparing values with PartialEq.
+    ///
+    /// # Arguments
+    ///
+    /// * 'client' - shared client. Each poll is one request through its rate limiter.
+    /// * 'endpoint' - path to fetch, such as '/products/ETH-USD/stats'.
+    /// * 'interval' - time between polls.
+    pub fn spawn(client: Arc<CoinbasePublicClient>, endpoint: impl Into<String>, interval: Duration) -> Self
+    where
+        T: PartialEq,
+    {
+        Self::spawn_with(client, endpoint, interval, |previous, current| previous == current)
+    }
+
+    /// Like spawn, with 'same' deciding whether two values are equal, such as to only compare
+    /// the fields a caller cares about.
+    pub fn spawn_with(
+        client: Arc<CoinbasePublicClient>,
+        endpoint: impl Into<String>,
+        interval: Duration,
+        same: impl Fn(&T, &T) -> bool + Send + 'static
+    ) -> Self {
+        let endpoint = endpoint.into();
+        let latest: Arc<RwLock<Option<T>>> = Arc::new(RwLock::new(None));
+        let shared_latest = latest.clone();
+
+        let changes = Background::spawn(CHANNEL_CAPACITY, |changes| async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticks.tick().await;
+                let Ok(current) = client.get_typed::<T>(&endpoint, None).await else {
+                    continue;
+                };
+                let mut latest = shared_latest.write().unwrap();
+                if latest.as_ref().is_some_and(|previous| same(previous, &current)) {
+                    continue;
+                }
+                *latest = Some(current.clone());
+                changes.publish(current);
+            }
+        });
+
+        Self { latest, changes }
+    }
+
+    /// Receives each new value. A subscriber more than 256 values behind skips to the newest
+    /// ones after a Lagged error.
+    pub fn subscribe(&self) -> broadcast::Receiver<T> {
+        self.changes.subscribe()
+    }
+
+    /// Most recent value emitted, if any.
+    pub fn latest(&self) -> Option<T> {
+        self.latest.read().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Stats;
+
+    #[tokio::test]
+    async fn test_emits_only_changes() {
+        let client = Arc::new(CoinbasePublicClient::builder()
+            .offline(crate::testing::CannedResponses::new()
+                .with("/products/ETH-USD/stats", include_str!("../tests/fixtures/products_eth-usd_stats.json")))
+            .build());
+
+        let watcher: Watcher<Stats> = Watcher::spawn(client.clone(), "/products/ETH-USD/stats", Duration::from_millis(5));
+        let mut changes = watcher.subscribe();
+        let first = changes.recv().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(matches!(changes.try_recv(), Err(broadcast::error::TryRecvError::Empty)));
+        assert_eq!(watcher.latest(), Some(first));
+
+        let noisy: Watcher<Stats> = Watcher::spawn_with(client, "/products/ETH-USD/stats", Duration::from_millis(5), |_, _| false);
+        let mut changes = noisy.subscribe();
+        changes.recv().await.unwrap();
+        changes.recv().await.unwrap();
+    }
+}